@@ -9,7 +9,7 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
 
 	// This is a nested vector of all the arguments for each of the functions in `fn_name`. It does
-	// not include the `self` or `caller: T::AccountId` parameter, which we always assume are the
+	// not include the `self` or `origin: OriginFor<T>` parameter, which we always assume are the
 	// first two parameters to these calls.
 	let args_name = methods
 		.iter()
@@ -23,13 +23,58 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// The `Call` enum gets `Clone`, `PartialEq` and `Debug` implementations, so extrinsics can be
+	// copied, compared and printed.
+	let call_enum = syn::Ident::new("Call", proc_macro2::Span::call_site());
+	let call_generics: syn::Generics = syn::parse_quote!(<T: Config>);
+	let call_variants = methods
+		.iter()
+		.map(|method| {
+			let args = method.args.iter().map(|(name, type_)| (name.clone(), (**type_).clone()));
+			(method.name.clone(), args.collect::<Vec<_>>())
+		})
+		.collect::<Vec<_>>();
+	let call_traits_impl =
+		crate::no_bound::expand_no_bound(&call_enum, &call_generics, &call_variants);
+
+	// This is the weight expression of each call, from its `#[weight(..)]` attribute. It can use the
+	// arguments of the call, by reference.
+	let weight = methods.iter().map(|method| &method.weight).collect::<Vec<_>>();
+
+	// This is the stable index of each call in the encoding of `Call`: its position in the impl.
+	// The parser rejects pallets with more calls than there are indices.
+	let call_index = (0..=u8::MAX).take(methods.len()).collect::<Vec<_>>();
+	// Every argument type, to bound the `Encode` and `Decode` implementations.
+	let all_args_type = args_type.iter().flatten().collect::<Vec<_>>();
+	// The serde derives get the same bounds. serde can infer bounds on `T::AccountId`, but not on
+	// types wrapping it like `Box<T::RuntimeCall>`.
+	let serde_bound = |bound: &str| {
+		all_args_type
+			.iter()
+			.map(|type_| format!("{}: {}", quote!(#type_), bound))
+			.collect::<Vec<_>>()
+			.join(", ")
+	};
+	// The names of the arguments and the doc comments of each call, for the metadata of the
+	// pallet. The names of the types of the arguments are only known once `T` is the runtime.
+	let args_name_str = args_name
+		.iter()
+		.map(|names| names.iter().map(|name| name.to_string()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let docs = methods.iter().map(|method| &method.docs).collect::<Vec<_>>();
+
+	let serialize_bound = serde_bound("::serde::Serialize");
+	let deserialize_bound = serde_bound("::serde::Deserialize<'de>");
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
-	// and the `Dispatch` trait logic to route a `caller` to access those functions.
+	// and the `Dispatch` trait logic to route an `origin` to access those functions.
 	let dispatch_impl = quote! {
 		// The callable functions exposed by this pallet.
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(::serde::Serialize, ::serde::Deserialize)]
+		#[serde(bound(serialize = #serialize_bound, deserialize = #deserialize_bound))]
 		pub enum Call<T: Config> {
 			#(
 				#fn_name { #( #args_name: #args_type),* },
@@ -37,18 +82,22 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		}
 
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
-		// appropriate function call with all arguments, including the `caller`.
+		// appropriate function call with all arguments, including the `origin`.
 		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
-			type Caller = T::AccountId;
+			type Origin = crate::system::OriginFor<T>;
 			type Call = Call<T>;
 
-			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
+			fn weight(call: &Self::Call) -> crate::support::Weight {
+				call.weight()
+			}
+
+			fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> crate::support::DispatchResult {
 				match call {
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
 							self.#fn_name(
-								// Note that we assume the first argument of every call is the `caller`.
-								caller,
+								// Note that we assume the first argument of every call is the `origin`.
+								origin,
 								#( #args_name ),*
 							)?;
 						},
@@ -57,6 +106,86 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 				Ok(())
 			}
 		}
+
+		impl<T: Config> Call<T> {
+			/// The name of this call, which is the name of its function.
+			pub fn name(&self) -> &'static str {
+				match self {
+					#(
+						Call::#fn_name { .. } => stringify!(#fn_name),
+					)*
+				}
+			}
+
+			/// Describe every call of this pallet, in the order of their indices.
+			pub fn metadata() -> Vec<crate::metadata::CallMetadata>
+			where
+				#( #all_args_type: crate::metadata::TypeInfo ),*
+			{
+				vec![
+					#(
+						crate::metadata::CallMetadata {
+							name: stringify!(#fn_name),
+							index: #call_index,
+							args: vec![
+								#(
+									crate::metadata::FieldMetadata {
+										name: #args_name_str,
+										type_name: <#args_type as crate::metadata::TypeInfo>::type_name(),
+									}
+								),*
+							],
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
+
+			/// The weight of this call, as declared by its `#[weight(..)]` attribute.
+			#[allow(unused_variables)]
+			pub fn weight(&self) -> crate::support::Weight {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => #weight,
+					)*
+				}
+			}
+		}
+
+		#call_traits_impl
+
+		// A call is encoded as its index followed by its arguments.
+		impl<T: Config> crate::codec::Encode for Call<T>
+		where
+			#( #all_args_type: crate::codec::Encode, )*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							dest.push(#call_index);
+							#( crate::codec::Encode::encode_to(#args_name, dest); )*
+						},
+					)*
+				}
+			}
+		}
+
+		impl<T: Config> crate::codec::Decode for Call<T>
+		where
+			#( #all_args_type: crate::codec::Decode, )*
+		{
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+				match <u8 as crate::codec::Decode>::decode(input)? {
+					#(
+						#call_index => Ok(Call::#fn_name {
+							#( #args_name: crate::codec::Decode::decode(input)? ),*
+						}),
+					)*
+					index => Err(crate::codec::Error::InvalidVariant(index)),
+				}
+			}
+		}
 	};
 
 	// Return the generated code.
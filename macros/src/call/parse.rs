@@ -4,7 +4,7 @@ use syn::spanned::Spanned;
 // Custom keywords we match to when parsing the calls in a pallet.
 mod keyword {
 	syn::custom_keyword!(T);
-	syn::custom_keyword!(AccountId);
+	syn::custom_keyword!(OriginFor);
 }
 
 /// This object will collect all the information we need to keep while parsing the callable
@@ -25,6 +25,10 @@ pub struct CallVariantDef {
 	pub name: syn::Ident,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The weight of the function, from its `#[weight(..)]` attribute.
+	pub weight: syn::Expr,
+	/// The lines of the doc comments of the function.
+	pub docs: Vec<String>,
 }
 
 impl CallDef {
@@ -59,22 +63,31 @@ impl CallDef {
 					},
 				}
 
-				// The second argument should be the `caller: T::AccountId` argument.
+				// The second argument should be the `origin: OriginFor<T>` argument.
 				match method.sig.inputs.iter().skip(1).next() {
 					Some(syn::FnArg::Typed(arg)) => {
 						// Here we specifically check that this argument is as we expect for
-						// `caller: T::AccountId`.
-						check_caller_arg(arg)?;
+						// `origin: OriginFor<T>`.
+						check_origin_arg(arg)?;
 					},
 					_ => {
-						let msg = "Invalid call, second argument should be `caller: T::AccountId`";
+						let msg = "Invalid call, second argument should be `origin: OriginFor<T>`";
 						return Err(syn::Error::new(method.sig.span(), msg))
 					},
 				}
 
 				let fn_name = method.sig.ident.clone();
 
-				// Parsing the rest of the args. Skipping 2 for `self` and `caller`.
+				// Every callable function must declare its weight.
+				let weight = match method.attrs.iter().find(|attr| attr.path().is_ident("weight")) {
+					Some(attr) => attr.parse_args::<syn::Expr>()?,
+					None => {
+						let msg = "Invalid call, missing `#[weight(..)]` attribute";
+						return Err(syn::Error::new(method.sig.span(), msg))
+					},
+				};
+
+				// Parsing the rest of the args. Skipping 2 for `self` and `origin`.
 				for arg in method.sig.inputs.iter().skip(2) {
 					// All arguments should be typed.
 					let arg = if let syn::FnArg::Typed(arg) = arg {
@@ -95,8 +108,15 @@ impl CallDef {
 					args.push((arg_ident, arg.ty.clone()));
 				}
 
+				// Calls are identified by their index in a single byte.
+				if methods.len() > u8::MAX as usize {
+					let msg = "Invalid pallet::call, a pallet can have at most 256 calls";
+					return Err(syn::Error::new(method.sig.span(), msg))
+				}
+
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				let docs = crate::metadata::docs(&method.attrs);
+				methods.push(CallVariantDef { name: fn_name, args, weight, docs });
 			}
 		}
 
@@ -105,33 +125,46 @@ impl CallDef {
 	}
 }
 
-/// Check caller arg is exactly: `caller: T::AccountId`.
+/// Remove the `#[weight(..)]` attributes from the callable functions. They are only read by this
+/// macro, and the compiler would reject them in the final code.
+pub fn strip_weight_attrs(item: &mut syn::Item) {
+	if let syn::Item::Impl(item_impl) = item {
+		for item in item_impl.items.iter_mut() {
+			if let syn::ImplItem::Fn(method) = item {
+				method.attrs.retain(|attr| !attr.path().is_ident("weight"));
+			}
+		}
+	}
+}
+
+/// Check origin arg is exactly: `origin: OriginFor<T>`.
 ///
 /// This is kept strict to keep the code simple.
-pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<()> {
+pub fn check_origin_arg(arg: &syn::PatType) -> syn::Result<()> {
 	pub struct CheckDispatchableFirstArg;
 	impl syn::parse::Parse for CheckDispatchableFirstArg {
 		fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+			input.parse::<keyword::OriginFor>()?;
+			input.parse::<syn::Token![<]>()?;
 			input.parse::<keyword::T>()?;
-			input.parse::<syn::Token![::]>()?;
-			input.parse::<keyword::AccountId>()?;
+			input.parse::<syn::Token![>]>()?;
 			Ok(Self)
 		}
 	}
 
-	// This checks the arg name is `caller` or `_caller`.
+	// This checks the arg name is `origin` or `_origin`.
 	if let syn::Pat::Ident(ident) = &*arg.pat {
-		// We also support the name as `_caller` for when the variable is unused.
-		if &ident.ident != "caller" && &ident.ident != "_caller" {
-			let msg = "Invalid name for second parameter: expected `caller: T::AccountId`";
+		// We also support the name as `_origin` for when the variable is unused.
+		if &ident.ident != "origin" && &ident.ident != "_origin" {
+			let msg = "Invalid name for second parameter: expected `origin: OriginFor<T>`";
 			return Err(syn::Error::new(ident.span(), msg))
 		}
 	}
 
-	// This checks the type is `T::AccountId` with `CheckDispatchableFirstArg`
+	// This checks the type is `OriginFor<T>` with `CheckDispatchableFirstArg`
 	let ty = &arg.ty;
 	syn::parse2::<CheckDispatchableFirstArg>(ty.to_token_stream()).map_err(|e| {
-		let msg = "Invalid type for second parameter: expected `caller: T::AccountId`";
+		let msg = "Invalid type for second parameter: expected `origin: OriginFor<T>`";
 		let mut err = syn::Error::new(ty.span(), msg);
 		err.combine(e);
 		err
@@ -0,0 +1,59 @@
+use super::parse::EventDef;
+use quote::quote;
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_event(def: EventDef) -> proc_macro2::TokenStream {
+	let EventDef { event_enum, generics, variants } = def;
+
+	// The names, fields and doc comments of each event, for the metadata of the pallet. The names
+	// of the types of the fields are only known once `T` is the runtime.
+	let variant_name = variants.iter().map(|variant| variant.name.to_string()).collect::<Vec<_>>();
+	let fields_name = variants
+		.iter()
+		.map(|variant| variant.fields.iter().map(|(name, _)| name.to_string()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let fields_type = variants
+		.iter()
+		.map(|variant| variant.fields.iter().map(|(_, type_)| type_).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let all_fields_type = fields_type.iter().flatten().collect::<Vec<_>>();
+	let docs = variants.iter().map(|variant| &variant.docs).collect::<Vec<_>>();
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let metadata_impl = quote! {
+		impl #impl_generics #event_enum #ty_generics #where_clause {
+			/// Describe every event of this pallet.
+			pub fn metadata() -> Vec<crate::metadata::EventMetadata>
+			where
+				#( #all_fields_type: crate::metadata::TypeInfo ),*
+			{
+				vec![
+					#(
+						crate::metadata::EventMetadata {
+							name: #variant_name,
+							fields: vec![
+								#(
+									crate::metadata::FieldMetadata {
+										name: #fields_name,
+										type_name: <#fields_type as crate::metadata::TypeInfo>::type_name(),
+									}
+								),*
+							],
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
+		}
+	};
+
+	let variants = variants
+		.into_iter()
+		.map(|variant| (variant.name, variant.fields))
+		.collect::<Vec<_>>();
+	let traits_impl = crate::no_bound::expand_no_bound(&event_enum, &generics, &variants);
+
+	quote! {
+		#traits_impl
+		#metadata_impl
+	}
+}
@@ -0,0 +1,57 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Event` enum of
+/// a pallet.
+#[derive(Debug)]
+pub struct EventDef {
+	/// This is the name of the event enum. We mostly assume it is `Event`.
+	pub event_enum: syn::Ident,
+	/// The generics of the event enum, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is a list of the events which can be deposited by this pallet. See `EventVariantDef`.
+	pub variants: Vec<EventVariantDef>,
+}
+
+/// This is the metadata we keep about each event variant.
+#[derive(Debug)]
+pub struct EventVariantDef {
+	/// The variant name.
+	pub name: syn::Ident,
+	/// Information on the fields of the variant: `(name, type)`. Empty for unit variants.
+	pub fields: Vec<(syn::Ident, syn::Type)>,
+	/// The lines of the doc comments of the variant.
+	pub docs: Vec<String>,
+}
+
+impl EventDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::event, expected item enum"))
+		};
+
+		let mut variants = vec![];
+		for variant in item_enum.variants {
+			// We only support struct-like and unit variants, so every field has a name.
+			let fields = match variant.fields {
+				syn::Fields::Named(fields) => fields
+					.named
+					.into_iter()
+					.map(|field| (field.ident.expect("named fields have an ident"), field.ty))
+					.collect(),
+				syn::Fields::Unit => vec![],
+				syn::Fields::Unnamed(fields) => {
+					let msg = "Invalid pallet::event, variant fields must be named";
+					return Err(syn::Error::new(fields.span(), msg))
+				},
+			};
+
+			let docs = crate::metadata::docs(&variant.attrs);
+			variants.push(EventVariantDef { name: variant.ident, fields, docs });
+		}
+
+		Ok(Self { event_enum: item_enum.ident, generics: item_enum.generics, variants })
+	}
+}
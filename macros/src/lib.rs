@@ -1,6 +1,28 @@
 mod call;
+mod event;
+mod metadata;
+mod no_bound;
 mod runtime;
 
+/// Expand the callable functions of a pallet.
+///
+/// This generates:
+/// - `enum Call` - with one variant per function of the `impl`, holding the arguments of the
+///   function except `self` and `origin`. It implements `Clone`, `PartialEq`, `Debug`,
+///   serde's `Serialize` and `Deserialize`, and `codec::Encode` and `codec::Decode`, where each call
+///   is identified by its position in the `impl`, in a single byte, so a pallet can have at most
+///   256 calls. Only add new functions at the end, or encoded calls change meaning.
+/// - `fn name()` on `Call` - the name of the call, which is the name of its function.
+/// - `fn metadata()` on `Call` - a `metadata::CallMetadata` for each function, with its name,
+///   index, doc comments and the names and types of its arguments. The types are described by
+///   `metadata::TypeInfo` once `T` is the runtime, like `u128` for `T::Balance`, so every type of
+///   an argument must implement it.
+/// - `fn weight()` on `Call` - the weight of the call, which each function must declare with a
+///   `#[weight(..)]` attribute. The weight expression can use the arguments of the function, by
+///   reference. The attribute is removed from the final code.
+/// - implements the trait `support::Dispatch` for the pallet, to route a `Call` to its function,
+///   along with the origin of the call. Every function takes `origin: OriginFor<T>` right after
+///   `self`.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -9,22 +31,94 @@ pub fn call(
 	call::call(attr, item)
 }
 
+/// Expand the `Event` enum of a pallet.
+///
+/// This generates `Clone`, `PartialEq` and `Debug` implementations for the enum. Unlike
+/// `#[derive]`, they are bounded on the types of the fields rather than on `T`, so an
+/// `Event<T: Config>` can be compared and printed without the config type itself implementing
+/// those traits.
+///
+/// It also generates `fn metadata()` on the enum, with a `metadata::EventMetadata` for each
+/// variant: its name, doc comments and the names and types of its fields. The types are described
+/// like the types of the arguments of calls.
+#[proc_macro_attribute]
+pub fn event(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	event::event(attr, item)
+}
+
 /// Expand the `Runtime` definition.
 ///
 /// This generates function implementations on `Runtime`:
-/// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
+/// - `fn from_genesis()` - which generates a new instance of the runtime, by building all the
+///   pallets included in the runtime from their initial state, and seals it as the genesis block.
+/// - `fn seal_genesis()` - which records the header of the genesis block, over the initial state of
+///   the runtime, in the system pallet. Its hash is the genesis hash of the chain.
+/// - `fn validate_transaction()` - which checks an extrinsic against the current state without
+///   changing it, for example before adding it to a transaction pool.
+/// - `fn initialize_block()`, `fn apply_extrinsic()`, `fn finalize_block()` and `fn state_root()` -
+///   the steps of executing a block. Every pallet, including system, must implement
+///   `support::Hooks`: its `on_initialize` and `on_finalize` hooks run at the start and at the end
+///   of every block, in the order the pallets are declared, and their weight counts toward the
+///   block.
+/// - `fn storage()` and `fn restore_storage()` - which read and replace the storage entries of all
+///   the pallets, so the state of the runtime can be saved and loaded again. Restoring fails, and
+///   changes nothing, if an entry does not decode as the type of its item. Every pallet must
+///   implement `storage::PalletStorage`.
+/// - `fn prove()` - which proves that a storage entry is in the current state, for clients which
+///   only know the state root of the last imported block.
+/// - `fn metadata()` - which describes the runtime as a `metadata::RuntimeMetadata`: each pallet
+///   with its index, calls, events and errors, from the `fn metadata()` of its `Call` and `Event`
+///   and the `PalletError::VARIANTS` of its `Error`, with their names and docs.
+/// - `fn build_block()` - which authors the next block from a list of candidate extrinsics, leaving
+///   out the invalid ones and stopping at the maximum block weight or length.
 /// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
-///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   basic actions like incrementing the block number and checking the header of the block against
+///   the last imported block, the extrinsics of the block and the resulting state. A block which
+///   exceeds the maximum block weight or length is invalid, and so is a block with an extrinsic
+///   which is not signed by its caller, which does not use the next nonce of its caller, or whose
+///   caller cannot pay its fee. Fees are charged through `support::TransactionPayment`, which the
+///   runtime must implement.
+///   Each extrinsic is dispatched in a storage transaction, so a failing extrinsic leaves no
+///   changes behind except its fee.
+///   Events deposited by the pallets during each extrinsic are collected into the system pallet.
+///   The block is executed in a storage transaction, so a block which is not valid leaves the state
+///   as it was.
+///   It returns a receipt for each extrinsic, with its caller, the result of its call, its weight
+///   and its events, which needs `types::BlockReceipt` and `types::ExtrinsicReceipt`.
+///   The block, each of its extrinsics and each call they dispatch run in a `trace::Span`, which is
+///   sent to the subscriber set with `trace::set_subscriber`, if any.
+///
+/// The state root is the root of a Merkle tree over the storage entries of all the pallets, see
+/// `merkle::root`. Every pallet, including system, is built by `fn new()` and
+/// `GenesisConfig::build()` over the `storage::Storage` shared by the runtime, whose backend is
+/// `system::Config::Backend`. Transactions are run by that backend.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
 /// - `enum RuntimeCall` - an "outer"-enum representing the accumulation of all possible calls to
+///   all pallets. The system pallet is not included. It implements serde's `Serialize` and
+///   `Deserialize`, and `codec::Encode` and `codec::Decode`, where each pallet is identified by its
+///   index in the runtime. Calls nested deeper than `system::Config::MAX_CALL_DEPTH` fail to
+///   decode, and are not valid transactions.
+/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet, with the
+///   weight of each call from the `fn weight()` of its pallet `Call`. Dispatching only routes the
+///   call: checking the signature and nonce of an extrinsic, incrementing the nonce, counting the
+///   weight toward the block and charging the fee happen in `fn apply_extrinsic()`, which also
+///   collects the events of the call into its receipt, while the hooks of the pallets run in
+///   `fn initialize_block()` and `fn finalize_block()`. The system pallet is not included. Every
+///   pallet must implement `support::DispatchQueue`: the calls it queues with another origin are
+///   dispatched right after the call to the pallet, each in a storage transaction, and their
+///   results are handed back to it.
+/// - `struct GenesisConfig` - the initial state of the runtime, with one field per pallet holding the
+///   `GenesisConfig` declared by that pallet's module, including system. It can be read from JSON.
+/// - `enum RuntimeEvent` - an "outer"-enum representing the accumulation of all possible events of
 ///   all pallets. The system pallet is not included.
-/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
-///   logic like incrementing the nonce of the user is included in the generated code. The system
-///   pallet is not included.
+/// - implements the trait `support::PalletIndex` for the `Error` enum of each pallet, so pallet
+///   errors know the index of the pallet they come from. The system pallet is index 0, which its
+///   `Error` declares itself.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
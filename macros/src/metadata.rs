@@ -0,0 +1,15 @@
+/// The lines of the doc comments in `attrs`, without the leading space of each line.
+pub fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("doc"))
+		.filter_map(|attr| match &attr.meta {
+			syn::Meta::NameValue(syn::MetaNameValue {
+				value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+				..
+			}) => Some(doc.value()),
+			_ => None,
+		})
+		.map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+		.collect()
+}
@@ -0,0 +1,96 @@
+use quote::quote;
+
+/// Generate `Clone`, `PartialEq` and `Debug` implementations for an enum whose variants
+/// all have named fields (or none at all), like the `Call` and `Event` enums of a pallet.
+///
+/// Unlike `#[derive]`, the generated implementations are bounded on the types of the fields rather
+/// than on the type parameters. This matters for enums generic over `T: Config`, where `T` is the
+/// runtime or a test config that does not implement these traits itself.
+///
+/// `variants` is a list of `(variant name, [(field name, field type)])`.
+pub fn expand_no_bound(
+	enum_name: &syn::Ident,
+	generics: &syn::Generics,
+	variants: &[(syn::Ident, Vec<(syn::Ident, syn::Type)>)],
+) -> proc_macro2::TokenStream {
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// This is a vector of all the variant names.
+	let variant_name = variants.iter().map(|(name, _)| name).collect::<Vec<_>>();
+
+	// This is a nested vector of all the field names for each of the variants in `variant_name`.
+	let fields_name = variants
+		.iter()
+		.map(|(_, fields)| fields.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// This is a list of every field type, which we use to bound the generated trait impls.
+	let field_types = variants
+		.iter()
+		.flat_map(|(_, fields)| fields.iter().map(|(_, type_)| type_.clone()))
+		.collect::<Vec<_>>();
+	let predicates = where_clause.map(|clause| clause.predicates.iter().collect::<Vec<_>>());
+	let predicates = predicates.unwrap_or_default();
+
+	// The names of the fields of the other value when comparing two values in `PartialEq`.
+	let other_fields_name = fields_name
+		.iter()
+		.map(|names| {
+			names.iter().map(|name| quote::format_ident!("other_{}", name)).collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+
+	quote! {
+		impl #impl_generics ::core::clone::Clone for #enum_name #ty_generics
+		where
+			#( #predicates, )*
+			#( #field_types: ::core::clone::Clone, )*
+		{
+			fn clone(&self) -> Self {
+				match self {
+					#(
+						Self::#variant_name { #( #fields_name ),* } => Self::#variant_name {
+							#( #fields_name: ::core::clone::Clone::clone(#fields_name) ),*
+						},
+					)*
+				}
+			}
+		}
+
+		impl #impl_generics ::core::cmp::PartialEq for #enum_name #ty_generics
+		where
+			#( #predicates, )*
+			#( #field_types: ::core::cmp::PartialEq, )*
+		{
+			fn eq(&self, other: &Self) -> bool {
+				match (self, other) {
+					#(
+						(
+							Self::#variant_name { #( #fields_name ),* },
+							Self::#variant_name { #( #fields_name: #other_fields_name ),* },
+						) => true #( && #fields_name == #other_fields_name )*,
+					)*
+					#[allow(unreachable_patterns)]
+					_ => false,
+				}
+			}
+		}
+
+		impl #impl_generics ::core::fmt::Debug for #enum_name #ty_generics
+		where
+			#( #predicates, )*
+			#( #field_types: ::core::fmt::Debug, )*
+		{
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match self {
+					#(
+						Self::#variant_name { #( #fields_name ),* } => f
+							.debug_struct(stringify!(#variant_name))
+							#( .field(stringify!(#fields_name), #fields_name) )*
+							.finish(),
+					)*
+				}
+			}
+		}
+	}
+}
@@ -9,37 +9,309 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
 	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
+	// This is a vector of the modules the pallets are declared in, e.g. `balances` for
+	// `balances::Pallet<Runtime>`. We use it to reach the other items declared by each pallet.
+	let pallet_modules = pallets.iter().map(|(_, type_)| pallet_module(type_)).collect::<Vec<_>>();
+	// This is a vector of the index of each pallet in the runtime. Index 0 is `system`.
+	let pallet_indices = (1..=pallets.len() as u8).collect::<Vec<_>>();
 
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
-			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
-			fn new() -> Self {
-				Self {
+			// Create a new instance of the main Runtime, by building each pallet from its initial state
+			// in a new backend shared by all of them, and seal it as the genesis block.
+			fn from_genesis(genesis: GenesisConfig) -> Self {
+				let storage = crate::storage::Storage::default();
+				let mut runtime = Self {
 					// Since system is not included in the list of pallets, we manually add it here.
-					system: <system::Pallet::<Self>>::new(),
+					system: genesis.system.build(&storage),
 					#(
-						#pallet_names: <#pallet_types>::new()
+						#pallet_names: genesis.#pallet_names.build(&storage)
 					),*
-				}
+				};
+				runtime.seal_genesis();
+				runtime
+			}
+
+			// Seal the current state of the runtime as the genesis block. Its hash identifies the chain
+			// and is the parent of the first block. Call this once, after setting up the initial state.
+			fn seal_genesis(&mut self) {
+				let header = types::Header {
+					block_number: self.system.block_number(),
+					parent_hash: crate::support::Hash::default(),
+					state_root: self.state_root(),
+					extrinsics_root: crate::support::extrinsics_root::<types::Extrinsic>(&[]),
+				};
+				self.system.set_genesis_header(header);
 			}
 
-			// Execute a block of extrinsics. Increments the block number.
-			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
+			// Start a new block on top of the current state. Checks that the header follows the last
+			// imported block, increments the block number, clears the events of the previous block
+			// and runs the `on_initialize` hook of every pallet.
+			fn initialize_block(&mut self, header: &types::Header) -> crate::support::DispatchResult {
+				if header.block_number != self.system.block_number() + 1 {
+					return Err(system::Error::InvalidBlockNumber.into())
+				}
+				if header.parent_hash != self.system.parent_hash() {
+					return Err(system::Error::InvalidParentHash.into())
+				}
 				self.system.inc_block_number();
-				if block.header.block_number != self.system.block_number() {
-					return Err(&"block number does not match what is expected")
+				// Events are only kept for the current block.
+				self.system.reset_events();
+				self.system.reset_block_weight();
+				let block_number = self.system.block_number();
+				let weight = crate::support::Hooks::on_initialize(&mut self.system, block_number);
+				self.system.register_mandatory_weight(weight);
+				#(
+					let weight = crate::support::Hooks::on_initialize(&mut self.#pallet_names, block_number);
+					self.system.register_mandatory_weight(weight);
+				)*
+				for event in self.take_events() {
+					self.system.deposit_event(system::Phase::Initialization, event);
 				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
+				Ok(())
+			}
+
+			// Finish the current block, after its extrinsics, by running the `on_finalize` hook of
+			// every pallet.
+			fn finalize_block(&mut self) {
+				let block_number = self.system.block_number();
+				let weight = crate::support::Hooks::on_finalize(&mut self.system, block_number);
+				self.system.register_mandatory_weight(weight);
+				#(
+					let weight = crate::support::Hooks::on_finalize(&mut self.#pallet_names, block_number);
+					self.system.register_mandatory_weight(weight);
+				)*
+				for event in self.take_events() {
+					self.system.deposit_event(system::Phase::Finalization, event);
+				}
+			}
+
+			// Check that an extrinsic can be applied on top of the current state: it is signed by its
+			// caller, the caller can pay its fee, and it uses the next nonce of the caller. This does
+			// not change anything, so it can be used to validate transactions before they are put in
+			// a block.
+			fn validate_transaction(&self, extrinsic: &types::Extrinsic) -> crate::support::DispatchResult {
+				// Weighing and dispatching a call recurses over its nested calls, so a call which is
+				// too deep to be decoded is not valid either, even if it was built in memory.
+				let encoded_call = crate::codec::Encode::encode(&extrinsic.call);
+				if crate::codec::decode_all::<RuntimeCall>(&encoded_call).is_err() {
+					return Err(system::Error::CallTooDeep.into())
+				}
+				if !extrinsic.verify(&self.system.genesis_hash()) {
+					return Err(system::Error::BadSignature.into())
+				}
+				let weight = <Self as crate::support::Dispatch>::weight(&extrinsic.call);
+				let length = crate::codec::Encode::encode(extrinsic).len();
+				let fee = <Self as crate::support::TransactionPayment<types::AccountId>>::fee(weight, length);
+				crate::support::TransactionPayment::can_pay_fee(self, &extrinsic.caller, fee)?;
+				self.system.check_nonce(&extrinsic.caller, extrinsic.nonce)
+			}
+
+			// Apply one extrinsic of the current block. An extrinsic which is not valid, or which
+			// does not fit in the block, returns an error without changing anything. Otherwise, the
+			// fee is withdrawn from the caller through `support::TransactionPayment`, and a failing
+			// call does not fail the block: its error is returned in the receipt of the extrinsic,
+			// and everything it wrote, including its events, is reverted. The fee is kept.
+			fn apply_extrinsic(
+				&mut self,
+				index: u32,
+				extrinsic: types::Extrinsic,
+			) -> Result<types::ExtrinsicReceipt, crate::support::DispatchError> {
+				let span_caller = extrinsic.caller.clone();
+				let span = || crate::trace::Span::Extrinsic { index, caller: format!("{:?}", span_caller) };
+				crate::trace::in_span(span, || {
+					self.validate_transaction(&extrinsic)?;
+					let length = crate::codec::Encode::encode(&extrinsic).len();
+					let support::Extrinsic { caller, call, .. } = extrinsic;
+					let weight = <Self as crate::support::Dispatch>::weight(&call);
+					let fee = <Self as crate::support::TransactionPayment<types::AccountId>>::fee(weight, length);
+					let backend = self.system.backend().clone();
+					crate::support::with_transaction(&backend, || {
+						self.system.register_weight(weight)?;
+						crate::support::TransactionPayment::withdraw_fee(self, &caller, fee)
+					})?;
 					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
-					});
+					let mut events = self.take_events();
+					let origin = crate::support::Origin::Signed(caller.clone());
+					let result = crate::support::with_transaction(&backend, || self.dispatch(origin, call));
+					// Events are not in storage, so those of a failing call are dropped here.
+					let call_events = self.take_events();
+					if result.is_ok() {
+						events.extend(call_events);
+					}
+					for event in &events {
+						self.system.deposit_event(system::Phase::ApplyExtrinsic(index), event.clone());
+					}
+					Ok(crate::support::ExtrinsicReceipt { index, caller, result, weight, events })
+				})
+			}
+
+			// The root of the Merkle tree over the storage of every pallet. Two nodes agree on the
+			// whole state of the runtime when they agree on this hash.
+			fn state_root(&self) -> crate::support::Hash {
+				crate::merkle::root(&self.storage())
+			}
+
+			// Execute a block of extrinsics. Checks the header against the last imported block, the
+			// extrinsics in the block and the resulting state, then records the block as imported.
+			//
+			// The block is executed in a storage transaction, which is only committed once the whole
+			// block is valid: a block which is not valid changes nothing.
+			//
+			// Returns the receipt of the block, with the outcome of each of its extrinsics.
+			fn execute_block(
+				&mut self,
+				block: types::Block,
+			) -> Result<types::BlockReceipt, crate::support::DispatchError> {
+				let number = block.header.block_number;
+				crate::trace::in_span(
+					|| crate::trace::Span::Block { number: format!("{:?}", number) },
+					|| {
+						let backend = self.system.backend().clone();
+						// Events are not in storage, so the events of the last imported block are
+						// put back by hand if this block is not valid.
+						let events = self.system.replace_events(Vec::new());
+						let result = crate::support::with_transaction(&backend, || self.apply_block(block));
+						if result.is_err() {
+							self.take_events();
+							self.system.replace_events(events);
+						}
+						result
+					},
+				)
+			}
+
+			// The steps of `execute_block`, applied directly to the current state. A block which is not
+			// valid may leave some of its changes behind.
+			fn apply_block(
+				&mut self,
+				block: types::Block,
+			) -> Result<types::BlockReceipt, crate::support::DispatchError> {
+				self.initialize_block(&block.header)?;
+				if block.header.extrinsics_root != crate::support::extrinsics_root(&block.extrinsics) {
+					return Err(system::Error::BadExtrinsicsRoot.into())
+				}
+				let length: usize = block.extrinsics.iter().map(|e| crate::codec::Encode::encode(e).len()).sum();
+				if length > <Self as system::Config>::MAX_BLOCK_LENGTH {
+					return Err(system::Error::BlockLengthExceeded.into())
+				}
+				let mut receipts = Vec::new();
+				for (i, extrinsic) in block.extrinsics.into_iter().enumerate() {
+					receipts.push(self.apply_extrinsic(i as u32, extrinsic)?);
+				}
+				self.finalize_block();
+				if block.header.state_root != self.state_root() {
+					return Err(system::Error::BadStateRoot.into())
+				}
+				self.system.set_parent_hash(block.header.hash());
+				Ok(crate::support::BlockReceipt { extrinsics: receipts })
+			}
+
+			// Author the next block on top of the current state, from a list of candidate extrinsics.
+			// The candidates are applied one at a time in a storage transaction which is reverted at
+			// the end, so the current state does not change. Candidates which are not valid are left
+			// out, and the block is finished at the first candidate which does not fit in the maximum
+			// block weight or length.
+			fn build_block(&mut self, candidates: Vec<types::Extrinsic>) -> types::Block {
+				let mut header = types::Header {
+					block_number: self.system.block_number() + 1,
+					parent_hash: self.system.parent_hash(),
+					state_root: crate::support::Hash::default(),
+					extrinsics_root: crate::support::Hash::default(),
+				};
+				let backend = self.system.backend().clone();
+				let events = self.system.replace_events(Vec::new());
+				backend.start_transaction();
+				self.initialize_block(&header).expect("the header follows the current state");
+
+				let mut extrinsics = Vec::new();
+				let mut length = 0;
+				for extrinsic in candidates {
+					let extrinsic_length = crate::codec::Encode::encode(&extrinsic).len();
+					if length + extrinsic_length > <Self as system::Config>::MAX_BLOCK_LENGTH {
+						break
+					}
+					match self.apply_extrinsic(extrinsics.len() as u32, extrinsic.clone()) {
+						Ok(_) => {
+							length += extrinsic_length;
+							extrinsics.push(extrinsic);
+						},
+						Err(e) if e == system::Error::BlockWeightExceeded.into() => break,
+						Err(_) => continue,
+					}
+				}
+
+				self.finalize_block();
+				header.extrinsics_root = crate::support::extrinsics_root(&extrinsics);
+				header.state_root = self.state_root();
+				backend.rollback_transaction();
+				self.system.replace_events(events);
+				types::Block { header, extrinsics }
+			}
+
+			// All the storage entries of every pallet, including system, in key order. Together they
+			// are the persistent state of the runtime: events only live for the current block.
+			fn storage(&self) -> crate::storage::Entries {
+				let mut storage = crate::storage::PalletStorage::storage(&self.system);
+				#(
+					storage.extend(crate::storage::PalletStorage::storage(&self.#pallet_names));
+				)*
+				storage.sort();
+				storage
+			}
+
+			// Prove that the entry under the storage key `key` is in the current state, whose root is
+			// the state root of the last imported block. Returns `None` if there is no such entry.
+			fn prove(&self, key: &[u8]) -> Option<crate::merkle::Proof> {
+				crate::merkle::prove(&self.storage(), key)
+			}
+
+			// Replace the state of every pallet, including system, with the entries of `storage`. Fails
+			// without changing anything if an entry does not decode as the type of its item.
+			fn restore_storage(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), crate::codec::Error> {
+				let backend = self.system.backend().clone();
+				crate::support::with_transaction(&backend, || {
+					crate::storage::PalletStorage::restore(&mut self.system, storage)?;
+					#(
+						crate::storage::PalletStorage::restore(&mut self.#pallet_names, storage)?;
+					)*
+					Ok(())
+				})
+			}
+
+			// Describe the pallets of the runtime, with their calls, events and errors.
+			fn metadata() -> crate::metadata::RuntimeMetadata {
+				crate::metadata::RuntimeMetadata {
+					pallets: vec![
+						// System has no calls and no events.
+						crate::metadata::PalletMetadata {
+							name: "system",
+							index: 0,
+							calls: vec![],
+							events: vec![],
+							errors: crate::metadata::errors::<system::Error>(),
+						},
+						#(
+							crate::metadata::PalletMetadata {
+								name: stringify!(#pallet_names),
+								index: #pallet_indices,
+								calls: #pallet_modules::Call::<#runtime_struct>::metadata(),
+								events: #pallet_modules::Event::<#runtime_struct>::metadata(),
+								errors: crate::metadata::errors::<#pallet_modules::Error>(),
+							}
+						),*
+					],
 				}
-				Ok(())
+			}
+
+			// Take the events deposited by every pallet, wrapped in the `RuntimeEvent` enum.
+			fn take_events(&mut self) -> Vec<RuntimeEvent> {
+				let mut events = Vec::new();
+				#(
+					events.extend(self.#pallet_names.take_events().into_iter().map(RuntimeEvent::#pallet_names));
+				)*
+				events
 			}
 		}
 	};
@@ -51,21 +323,86 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
 		pub enum RuntimeCall {
 			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
 		}
 
+		// A runtime call is encoded as the index of the pallet followed by the pallet call.
+		impl crate::codec::Encode for RuntimeCall {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							dest.push(#pallet_indices);
+							crate::codec::Encode::encode_to(call, dest);
+						}
+					),*
+				}
+			}
+		}
+
+		// Calls can hold other calls, so decoding stops at `MAX_CALL_DEPTH` nested calls.
+		impl crate::codec::Decode for RuntimeCall {
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+				let max_depth = <#runtime_struct as system::Config>::MAX_CALL_DEPTH;
+				crate::codec::decode_nested(max_depth, || match <u8 as crate::codec::Decode>::decode(input)? {
+					#(
+						#pallet_indices => Ok(RuntimeCall::#pallet_names(crate::codec::Decode::decode(input)?)),
+					)*
+					index => Err(crate::codec::Error::InvalidVariant(index)),
+				})
+			}
+		}
+
+		// In the metadata, a call which holds another call describes it by the name of this enum.
+		impl crate::metadata::TypeInfo for RuntimeCall {
+			fn type_name() -> String {
+				"RuntimeCall".to_string()
+			}
+		}
+
+		// The initial state of the runtime: the genesis config of each pallet, including system. It
+		// can be read from JSON, where a missing pallet starts empty.
+		#[derive(Default, serde::Serialize, serde::Deserialize)]
+		#[serde(default, deny_unknown_fields)]
+		pub struct GenesisConfig {
+			pub system: system::GenesisConfig<#runtime_struct>,
+			#(
+				pub #pallet_names: #pallet_modules::GenesisConfig<#runtime_struct>
+			),*
+		}
+
+		// These are all the events which can be deposited by the pallets.
+		// Note that it is just an accumulation of the events declared by each pallet.
+		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, PartialEq)]
+		pub enum RuntimeEvent {
+			#( #pallet_names(#pallet_modules::Event<#runtime_struct>) ),*
+		}
+
 		impl crate::support::Dispatch for #runtime_struct {
-			type Caller = <Runtime as system::Config>::AccountId;
+			type Origin = system::OriginFor<#runtime_struct>;
 			type Call = RuntimeCall;
-			// Dispatch a call on behalf of a caller. Increments the caller's nonce.
+			// The weight of a call is the weight declared by the pallet for it.
+			fn weight(runtime_call: &Self::Call) -> crate::support::Weight {
+				match runtime_call {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							<#pallet_types as crate::support::Dispatch>::weight(call)
+						}
+					),*
+				}
+			}
+
+			// Dispatch a call on behalf of an origin.
 			//
 			// Dispatch allows us to identify which underlying pallet call we want to execute.
-			// Note that we extract the `caller` from the extrinsic, and use that information
-			// to determine who we are executing the call on behalf of.
+			// Note that extrinsics are dispatched with the `Signed` origin of their caller, which
+			// determines who we are executing the call on behalf of.
 			fn dispatch(
 				&mut self,
-				caller: Self::Caller,
+				origin: Self::Origin,
 				runtime_call: Self::Call,
 			) -> crate::support::DispatchResult {
 				// This match statement will allow us to correctly route `RuntimeCall`s
@@ -73,7 +410,32 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 				match runtime_call {
 					#(
 						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
+							let (name, span_origin) = (call.name(), origin.clone());
+							let span = || crate::trace::Span::Call {
+								pallet: stringify!(#pallet_names),
+								call: name,
+								origin: format!("{:?}", span_origin),
+							};
+							crate::trace::in_span(span, || {
+								let result = self.#pallet_names.dispatch(origin, call);
+								// Dispatch the calls the pallet queued with another origin, if any, and
+								// hand their results back to it. They are dropped if the call itself
+								// failed.
+								let queued = crate::support::DispatchQueue::take_dispatches(&mut self.#pallet_names);
+								result?;
+								let backend = self.system.backend().clone();
+								for (origin, call) in queued {
+									let result = crate::support::with_transaction(&backend, || {
+										self.dispatch(origin.clone(), call)
+									});
+									crate::support::DispatchQueue::<_, RuntimeCall>::dispatched(
+										&mut self.#pallet_names,
+										origin,
+										result,
+									);
+								}
+								Ok(())
+							})?;
 						}
 					),*
 				}
@@ -82,10 +444,34 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		}
 	};
 
+	// This quote block tells every pallet error which pallet index it belongs to, so it can be
+	// turned into a `DispatchError`.
+	// The system pallet is always index 0, so it declares its index itself.
+	let pallet_index_impl = quote! {
+		#(
+			impl crate::support::PalletIndex for #pallet_modules::Error {
+				const INDEX: u8 = #pallet_indices;
+			}
+		)*
+	};
+
 	// We combine and return all the generated code.
 	quote! {
 		#dispatch_impl
 		#runtime_impl
+		#pallet_index_impl
 	}
 	.into()
 }
+
+/// Get the path of the module a pallet is declared in, by removing the last segment of the pallet
+/// type. For example `balances::Pallet<Runtime>` becomes `balances`.
+fn pallet_module(pallet_type: &syn::Type) -> syn::Path {
+	let mut path = match pallet_type {
+		syn::Type::Path(tp) => tp.path.clone(),
+		_ => panic!("not supported tokens"),
+	};
+	path.segments.pop();
+	path.segments.pop_punct();
+	path
+}
@@ -1,14 +1,108 @@
-use std::collections::BTreeMap;
 use num::{traits::CheckedAdd, CheckedSub, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{self, Decode, Encode};
+use crate::merkle::Proof;
+use crate::storage::{Entries, PalletStorage, Storage, StorageMap};
+use crate::support::{
+	ensure_root, ensure_signed, DispatchQueue, DispatchResult, Header, Hooks, PalletError,
+};
+use crate::system::OriginFor;
 
 pub trait Config: crate::system::Config {
-	type Balance: Zero + CheckedAdd + CheckedSub + Copy;
+	type Balance: Zero + CheckedAdd + CheckedSub + Copy + Encode + Decode;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(bound(serialize = "T::AccountId: Serialize, T::Balance: Serialize"))]
 pub struct Pallet<T: Config> {
     // A simple storage mapping from accounts (`String`) to their balances (`u128`).
-    balances: BTreeMap<T::AccountId, T::Balance>,
+    balances: StorageMap<T::AccountId, T::Balance, T::Backend>,
+    /// Events deposited by this pallet which have not been collected by the runtime yet.
+    #[serde(skip)]
+    events: Vec<Event<T>>,
+}
+
+/// The events which can be deposited by the balances pallet.
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `amount` was transferred from `from` to `to`.
+	Transfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+	/// `who` paid a fee of `amount` for an extrinsic.
+	FeePaid { who: T::AccountId, amount: T::Balance },
+	/// The balance of `who` was set to `amount` by the `Root` origin.
+	BalanceSet { who: T::AccountId, amount: T::Balance },
+}
+
+/// The errors which can be returned by the balances pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The sender does not have enough balance for the transfer.
+	InsufficientBalance,
+	/// The transfer would overflow the balance of the receiver.
+	BalanceOverflow,
+	/// The caller does not have enough balance to pay the fee of the extrinsic.
+	CannotPayFee,
+}
+
+impl PalletError for Error {
+	const VARIANTS: &'static [Self] = &[
+		Error::InsufficientBalance,
+		Error::BalanceOverflow,
+		Error::CannotPayFee,
+	];
+
+	fn index(&self) -> u8 {
+		*self as u8
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Error::InsufficientBalance => "InsufficientBalance",
+			Error::BalanceOverflow => "BalanceOverflow",
+			Error::CannotPayFee => "CannotPayFee",
+		}
+	}
+
+	fn docs(&self) -> &'static str {
+		match self {
+			Error::InsufficientBalance => "The sender does not have enough balance for the transfer.",
+			Error::BalanceOverflow => "The transfer would overflow the balance of the receiver.",
+			Error::CannotPayFee => "The caller does not have enough balance to pay the fee of the extrinsic.",
+		}
+	}
+}
+
+/// The initial state of the balances pallet.
+#[derive(Serialize, Deserialize)]
+#[serde(
+	bound(
+		serialize = "T::AccountId: Serialize, T::Balance: Serialize",
+		deserialize = "T::AccountId: Deserialize<'de>, T::Balance: Deserialize<'de>"
+	),
+	default,
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The initial balance of each account.
+	pub balances: Vec<(T::AccountId, T::Balance)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { balances: Vec::new() }
+	}
+}
+
+impl<T: Config> GenesisConfig<T> {
+	/// Build the balances pallet with this initial state, in `storage`.
+	pub fn build(self, storage: &Storage<T::Backend>) -> Pallet<T> {
+		let mut pallet = Pallet::new(storage);
+		for (who, amount) in self.balances {
+			pallet.set_balance(&who, amount);
+		}
+		pallet
+	}
 }
 
 #[macros::call]
@@ -16,54 +110,133 @@ impl<T: Config> Pallet<T> {
     /// Transfer `amount` from one account to another.
 	/// This function verifies that `from` has at least `amount` balance to transfer,
 	/// and that no mathematical overflows occur.
+    #[weight(1_000)]
     pub fn transfer(
         &mut self, 
-        caller: T::AccountId,
+        origin: OriginFor<T>,
         to: T::AccountId,
         amount: T::Balance,
-    ) -> crate::support::DispatchResult {
+    ) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
         let caller_balance = self.balance(&caller);
         let to_balance = self.balance(&to);
 
         let new_caller_balance = caller_balance
             .checked_sub(&amount)
-            .ok_or("insufficient balance")?;
+            .ok_or(Error::InsufficientBalance)?;
 
         let new_to_balance = to_balance
             .checked_add(&amount)
-            .ok_or("overflow when adding to balance")?;
+            .ok_or(Error::BalanceOverflow)?;
 
         self.set_balance(&caller, new_caller_balance);
         self.set_balance(&to, new_to_balance);
 
+        self.deposit_event(Event::Transfer { from: caller, to, amount });
+
+        Ok(())
+    }
+
+    /// Set the balance of `who` to `amount`, whatever it was before.
+    /// Only the `Root` origin can make this call.
+    #[weight(500)]
+    pub fn force_set_balance(
+        &mut self,
+        origin: OriginFor<T>,
+        who: T::AccountId,
+        amount: T::Balance,
+    ) -> DispatchResult {
+        ensure_root(origin)?;
+        self.set_balance(&who, amount);
+        self.deposit_event(Event::BalanceSet { who, amount });
         Ok(())
     }
 }
 
 impl<T: Config> Pallet<T> {
     /// Create a new instance of the balances module.
-    pub fn new () -> Self {
+    pub fn new (storage: &Storage<T::Backend>) -> Self {
         Self {
-            balances: BTreeMap::new()
+            balances: StorageMap::new(storage, "Balances", "Balances"),
+            events: Vec::new(),
         }
     }
 
+    /// Deposit an event, to be collected by the runtime at the end of the current extrinsic.
+    fn deposit_event(&mut self, event: Event<T>) {
+        self.events.push(event);
+    }
+
+    /// Take all the events deposited since the last time this was called.
+    pub fn take_events(&mut self) -> Vec<Event<T>> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// Check that `who` has enough balance to pay a fee, without withdrawing it.
+    pub fn can_pay_fee(&self, who: &T::AccountId, fee: T::Balance) -> DispatchResult {
+        match self.balance(who).checked_sub(&fee) {
+            Some(_) => Ok(()),
+            None => Err(Error::CannotPayFee.into()),
+        }
+    }
+
+    /// Withdraw the fee of an extrinsic from `who`. The fee is burned, and is not refunded if the
+    /// call of the extrinsic fails.
+    pub fn withdraw_fee(&mut self, who: &T::AccountId, fee: T::Balance) -> DispatchResult {
+        let new_balance = self.balance(who).checked_sub(&fee).ok_or(Error::CannotPayFee)?;
+        self.set_balance(who, new_balance);
+        self.deposit_event(Event::FeePaid { who: who.clone(), amount: fee });
+        Ok(())
+    }
+
     /// Set the balance of an account `who` to some `amount`.
     pub fn set_balance (&mut self, who: &T::AccountId, amount: T::Balance) {
-        self.balances.insert(who.clone(), amount);
+        self.balances.insert(who, &amount);
     }
 
     /// Get the balance of an account `who`.
 	/// If the account has no stored balance, we return zero.    
-    pub fn balance (&mut self, who: &T::AccountId) -> T::Balance {
-        *self.balances.get(who).unwrap_or(&T::Balance::zero())
+    pub fn balance (&self, who: &T::AccountId) -> T::Balance {
+        self.balances.get(who).unwrap_or_else(T::Balance::zero)
     }    
+
+    /// The storage key of the balance of `who`, to prove it with the runtime.
+    pub fn balance_key(&self, who: &T::AccountId) -> Vec<u8> {
+        self.balances.storage_key(who)
+    }
+
+    /// Check a proof of the balance of `who` against the state root of `header`, without any
+    /// state. Returns the balance it proves, or `None` if the proof is not valid. Accounts with
+    /// no stored balance cannot be proven.
+    pub fn verify_balance(
+        header: &Header<T::BlockNumber>,
+        who: &T::AccountId,
+        proof: &Proof,
+    ) -> Option<T::Balance> {
+        Self::new(&Storage::default()).balances.verify(&header.state_root, who, proof)
+    }
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+impl<T: Config, Call> DispatchQueue<OriginFor<T>, Call> for Pallet<T> {}
+
+impl<T: Config> PalletStorage for Pallet<T> {
+	fn storage(&self) -> Entries {
+		self.balances.entries()
+	}
+
+	fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		self.balances.restore(storage)
+	}
 }
 
 #[cfg(test)]
 mod tests {
     // use crate::balances::Pallet;
 
+    use super::{Error, Event};
+    use crate::support::{DispatchError, Origin};
     use crate::system;
 
     struct TestConfig;
@@ -71,6 +244,11 @@ mod tests {
         type AccountId = String;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Backend = crate::storage::MemoryBackend;
+        type RuntimeEvent = ();
+        const MAX_BLOCK_WEIGHT: crate::support::Weight = u64::MAX;
+        const MAX_BLOCK_LENGTH: usize = usize::MAX;
+        const MAX_CALL_DEPTH: u32 = 4;
     }
 
     impl super::Config for TestConfig {
@@ -79,7 +257,7 @@ mod tests {
 
     #[test]
 	fn init_balances() {
-		let mut balances: super::Pallet<TestConfig> = super::Pallet::new();
+		let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
 
 		assert_eq!(balances.balance(&"alice".to_string()), 0);
 		balances.set_balance(&"alice".to_string(), 100);
@@ -89,21 +267,25 @@ mod tests {
 
 	#[test]
 	fn transfer_balance() {
-		let mut balances: super::Pallet<TestConfig> = super::Pallet::new();
+		let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
 
 		assert_eq!(
-			balances.transfer("alice".to_string(), "bob".to_string(), 51),
-			Err("insufficient balance")
+			balances.transfer(Origin::Signed("alice".to_string()), "bob".to_string(), 51),
+			Err(Error::InsufficientBalance.into())
 		);
 
 		balances.set_balance(&"alice".to_string(), 100);
-		assert_eq!(balances.transfer("alice".to_string(), "bob".to_string(), 51), Ok(()));
+		assert_eq!(balances.transfer(Origin::Signed("alice".to_string()), "bob".to_string(), 51), Ok(()));
 		assert_eq!(balances.balance(&"alice".to_string()), 49);
 		assert_eq!(balances.balance(&"bob".to_string()), 51);
+		assert_eq!(
+			balances.take_events(),
+			vec![Event::Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: 51 }]
+		);
 
 		assert_eq!(
-			balances.transfer("alice".to_string(), "bob".to_string(), 51),
-			Err("insufficient balance")
+			balances.transfer(Origin::Signed("alice".to_string()), "bob".to_string(), 51),
+			Err(Error::InsufficientBalance.into())
 		);
 	}
 
@@ -112,15 +294,16 @@ mod tests {
         let alice = "alice".to_string();
         let bob = "bob".to_string();
 
-        let mut balances: super::Pallet<TestConfig> = super::Pallet::new();
+        let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
         
         balances.set_balance(&alice, 100);
 
-        let result = balances.transfer(alice.clone(), bob.clone(), 110);
+        let result = balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 110);
 
-        assert_eq!(result, Err("insufficient balance"));
+        assert_eq!(result, Err(Error::InsufficientBalance.into()));
         assert_eq!(balances.balance(&alice), 100);
         assert_eq!(balances.balance(&bob), 0);
+        assert!(balances.take_events().is_empty());
     }
 
     #[test]
@@ -128,15 +311,46 @@ mod tests {
         let alice = "alice".to_string();
         let bob = "bob".to_string();
 
-        let mut balances: super::Pallet<TestConfig> = super::Pallet::new();
+        let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
         
         balances.set_balance(&alice, 100);
         balances.set_balance(&bob, u128::MAX);
 
-        let result = balances.transfer(alice.clone(), bob.clone(), 1);
+        let result = balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 1);
 
-        assert_eq!(result, Err("overflow when adding to balance"));
+        assert_eq!(result, Err(Error::BalanceOverflow.into()));
         assert_eq!(balances.balance(&alice), 100);
         assert_eq!(balances.balance(&bob), u128::MAX);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn withdraw_fee() {
+        let alice = "alice".to_string();
+        let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
+        balances.set_balance(&alice, 10);
+
+        assert_eq!(balances.withdraw_fee(&alice, 4), Ok(()));
+        assert_eq!(balances.balance(&alice), 6);
+        assert_eq!(balances.take_events(), vec![Event::FeePaid { who: alice.clone(), amount: 4 }]);
+
+        assert_eq!(balances.withdraw_fee(&alice, 7), Err(Error::CannotPayFee.into()));
+        assert_eq!(balances.balance(&alice), 6);
+        assert!(balances.take_events().is_empty());
+    }
+
+    #[test]
+    fn force_set_balance() {
+        let alice = "alice".to_string();
+        let mut balances: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
+
+        assert_eq!(
+            balances.force_set_balance(Origin::Signed(alice.clone()), alice.clone(), 100),
+            Err(DispatchError::BadOrigin)
+        );
+        assert_eq!(balances.balance(&alice), 0);
+
+        assert_eq!(balances.force_set_balance(Origin::Root, alice.clone(), 100), Ok(()));
+        assert_eq!(balances.balance(&alice), 100);
+        assert_eq!(balances.take_events(), vec![Event::BalanceSet { who: alice, amount: 100 }]);
+    }
+}
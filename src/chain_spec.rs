@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{balances, sudo, GenesisConfig, Keyring};
+
+/// The description of a chain: its name, and the initial state of its runtime.
+///
+/// Chain specs are written as JSON, so every environment can start from its own initial state. For
+/// example, a chain where alice has 100 tokens and is the sudo key:
+///
+/// ```json
+/// {
+///   "name": "Development",
+///   "genesis": {
+///     "balances": { "balances": [["0x<alice>", 100]] },
+///     "sudo": { "key": "0x<alice>" }
+///   }
+/// }
+/// ```
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+	/// The name of the chain, for humans.
+	pub name: String,
+	/// The initial state of the runtime. Pallets which are not mentioned start empty.
+	#[serde(default)]
+	pub genesis: GenesisConfig,
+}
+
+/// The errors which can happen when loading a chain spec.
+#[derive(Debug)]
+pub enum Error {
+	/// The chain spec file could not be read.
+	Io(std::io::Error),
+	/// The chain spec is not valid JSON, or does not describe a chain.
+	Json(serde_json::Error),
+}
+
+impl core::fmt::Display for Error {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "cannot access chain spec: {}", e),
+			Error::Json(e) => write!(f, "invalid chain spec: {}", e),
+		}
+	}
+}
+
+impl ChainSpec {
+	/// Read a chain spec from JSON.
+	pub fn from_json(json: &str) -> Result<Self, Error> {
+		serde_json::from_str(json).map_err(Error::Json)
+	}
+
+	/// Read a chain spec from a JSON file.
+	pub fn load(path: &Path) -> Result<Self, Error> {
+		let json = std::fs::read_to_string(path).map_err(Error::Io)?;
+		Self::from_json(&json)
+	}
+
+	/// Write this chain spec to a JSON file.
+	pub fn save(&self, path: &Path) -> Result<(), Error> {
+		let json = serde_json::to_string_pretty(self).map_err(Error::Json)?;
+		std::fs::write(path, json).map_err(Error::Io)
+	}
+
+	/// The chain used for development, where alice has 100 tokens and is the sudo key.
+	pub fn dev() -> Self {
+		let alice = Keyring::Alice.public();
+		Self {
+			name: "Development".to_string(),
+			genesis: GenesisConfig {
+				balances: balances::GenesisConfig { balances: vec![(alice, 100)] },
+				sudo: sudo::GenesisConfig { key: Some(alice) },
+				..Default::default()
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ChainSpec, Error};
+	use crate::support::hex;
+	use crate::{types, Keyring, Runtime};
+
+	#[test]
+	fn build_runtime_from_json() {
+		let alice = hex::encode(&Keyring::Alice.public().0);
+		let bob = hex::encode(&Keyring::Bob.public().0);
+		let json = format!(
+			r#"{{
+				"name": "Testnet",
+				"genesis": {{
+					"system": {{ "block_number": 10 }},
+					"balances": {{ "balances": [["{alice}", 100], ["{bob}", 50]] }},
+					"proof_of_existence": {{ "claims": [["my_document", "{bob}"]] }}
+				}}
+			}}"#
+		);
+
+		let spec = ChainSpec::from_json(&json).unwrap();
+		assert_eq!(spec.name, "Testnet");
+		let mut runtime = Runtime::from_genesis(spec.genesis);
+		assert_eq!(runtime.system.block_number(), 10);
+		assert_eq!(runtime.balances.balance(&Keyring::Alice.public()), 100);
+		assert_eq!(runtime.balances.balance(&Keyring::Bob.public()), 50);
+		assert_eq!(
+			runtime.proof_of_existence.get_claim(&"my_document".to_string()),
+			Some(Keyring::Bob.public())
+		);
+		assert_eq!(runtime.sudo.key(), None);
+
+		// The first block follows the genesis block number.
+		let block = runtime.build_block(vec![]);
+		assert_eq!(block.header.block_number, 11);
+		assert_eq!(runtime.execute_block(block), Ok(types::BlockReceipt { extrinsics: vec![] }));
+	}
+
+	#[test]
+	fn reject_invalid_specs() {
+		let typo = r#"{ "name": "Testnet", "genesis": { "balance": { "balances": [] } } }"#;
+		assert!(matches!(ChainSpec::from_json(typo), Err(Error::Json(_))));
+
+		let bad_account = r#"{ "name": "Testnet", "genesis": { "sudo": { "key": "0x1234" } } }"#;
+		assert!(matches!(ChainSpec::from_json(bad_account), Err(Error::Json(_))));
+
+		let missing = ChainSpec::load(std::path::Path::new("does/not/exist.json"));
+		assert!(matches!(missing, Err(Error::Io(_))));
+	}
+}
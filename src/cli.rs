@@ -0,0 +1,438 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use clap::{Parser, Subcommand};
+
+use crate::chain_spec::{self, ChainSpec};
+use crate::codec::{self, Encode};
+use crate::store::{self, Store};
+use crate::support::{self, hex, DispatchError, PublicKey};
+use crate::transaction_pool::TransactionPool;
+use crate::{balances, proof_of_existence, trace, types, Keyring, Runtime, RuntimeCall};
+
+/// A node of the chain, which keeps its state in a data directory between runs.
+///
+/// A chain starts with `init`. Transactions are then submitted to the pool of the node with
+/// `submit`, and put in the next block with `produce-block`. Other nodes of the same chain import
+/// that block with `import-block`, from the file written by `produce-block --out`, or from the
+/// `blocks` directory of the producing node.
+#[derive(Debug, Parser)]
+pub struct Cli {
+	/// The directory the chain is saved in.
+	#[arg(long, global = true, default_value = "data")]
+	pub data_dir: PathBuf,
+	/// Print the execution of blocks, extrinsics and calls to stderr.
+	#[arg(long, global = true)]
+	pub trace: bool,
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Start a new chain in the data directory, from its genesis block.
+	Init {
+		/// The chain spec of the chain, as JSON. Defaults to the development chain, where alice has
+		/// 100 tokens and is the sudo key.
+		#[arg(long)]
+		genesis: Option<PathBuf>,
+	},
+	/// Sign a transaction with a development account, and add it to the pool.
+	Submit {
+		/// The development account which signs the transaction, like `alice`.
+		#[arg(long, value_parser = parse_keyring)]
+		from: Keyring,
+		/// The nonce of the transaction. Defaults to the next nonce of the signer, after its
+		/// transactions in the pool.
+		#[arg(long)]
+		nonce: Option<types::Nonce>,
+		#[command(subcommand)]
+		call: Call,
+	},
+	/// Build the next block from the transactions in the pool, and import it.
+	ProduceBlock {
+		/// A file to write the encoded block to, for other nodes to import.
+		#[arg(long)]
+		out: Option<PathBuf>,
+	},
+	/// Import an encoded block produced by another node of the chain.
+	ImportBlock {
+		/// The file holding the encoded block.
+		file: PathBuf,
+	},
+	/// Read the state of the chain after the last imported block.
+	#[command(subcommand)]
+	Query(Query),
+	/// Print the state of the chain as JSON.
+	ExportState,
+	/// Print the metadata of the runtime as JSON: its pallets, with their calls, events and errors.
+	Metadata,
+}
+
+/// The calls which can be submitted.
+#[derive(Debug, Subcommand)]
+pub enum Call {
+	/// Transfer tokens from the signer to another account.
+	Transfer {
+		/// The receiver: a development account, like `bob`, or a hex public key.
+		#[arg(long, value_parser = parse_account)]
+		to: types::AccountId,
+		/// The number of tokens to transfer.
+		#[arg(long)]
+		amount: types::Balance,
+	},
+	/// Claim some content on behalf of the signer.
+	Claim {
+		/// The content to claim.
+		content: types::Content,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Query {
+	/// The balance of an account.
+	Balance {
+		/// A development account, like `bob`, or a hex public key.
+		#[arg(value_parser = parse_account)]
+		account: types::AccountId,
+		/// Also print a proof of the balance, checked against the state root of the last block.
+		#[arg(long)]
+		prove: bool,
+	},
+	/// The owner of a claim, if any.
+	Claim {
+		/// The claimed content.
+		content: types::Content,
+		/// Also print a proof of the claim, checked against the state root of the last block.
+		#[arg(long)]
+		prove: bool,
+	},
+}
+
+/// The errors which can happen when running a command.
+#[derive(Debug)]
+pub enum Error {
+	/// The data directory has no chain, and `init` must be run first.
+	NotInitialized,
+	/// `init` was run on a data directory which already has a chain.
+	AlreadyInitialized,
+	/// The chain spec could not be read or written.
+	ChainSpec(chain_spec::Error),
+	/// The data directory could not be read or written.
+	Store(store::Error),
+	/// A block file could not be read.
+	Io(std::io::Error),
+	/// A block file does not hold a block.
+	Codec(codec::Error),
+	/// The pool rejected the submitted transaction.
+	InvalidTransaction(DispatchError),
+	/// The imported block is not a valid next block of the chain.
+	InvalidBlock(DispatchError),
+	/// There is nothing to prove, since the entry is not in storage.
+	NothingToProve,
+}
+
+impl core::fmt::Display for Error {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Error::NotInitialized => write!(f, "no chain in the data directory, run `init` first"),
+			Error::AlreadyInitialized => write!(f, "the data directory already has a chain"),
+			Error::ChainSpec(e) => write!(f, "{}", e),
+			Error::Store(e) => write!(f, "{}", e),
+			Error::Io(e) => write!(f, "cannot read block: {}", e),
+			Error::Codec(e) => write!(f, "invalid block encoding: {}", e),
+			Error::InvalidTransaction(e) => write!(f, "invalid transaction: {}", e),
+			Error::InvalidBlock(e) => write!(f, "invalid block: {}", e),
+			Error::NothingToProve => write!(f, "nothing to prove: the entry is not in storage"),
+		}
+	}
+}
+
+impl From<chain_spec::Error> for Error {
+	fn from(e: chain_spec::Error) -> Self {
+		Error::ChainSpec(e)
+	}
+}
+
+impl From<store::Error> for Error {
+	fn from(e: store::Error) -> Self {
+		Error::Store(e)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(e: std::io::Error) -> Self {
+		Error::Io(e)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(e: codec::Error) -> Self {
+		Error::Codec(e)
+	}
+}
+
+/// Run `cli`, printing its results to stdout.
+pub fn run(cli: Cli) -> Result<(), Error> {
+	if cli.trace {
+		trace::set_subscriber(Some(Rc::new(trace::Logger::default())));
+	}
+	let data_dir = &cli.data_dir;
+	match cli.command {
+		Command::Init { genesis } => {
+			let spec = match genesis {
+				Some(path) => ChainSpec::load(&path)?,
+				None => ChainSpec::dev(),
+			};
+			let runtime = init(data_dir, spec)?;
+			println!("Chain initialized in {}", data_dir.display());
+			println!("Genesis hash: {}", hex::encode(&runtime.system.genesis_hash()));
+		},
+		Command::Submit { from, nonce, call } => {
+			let mut node = Node::open(data_dir)?;
+			let call = match call {
+				Call::Transfer { to, amount } => RuntimeCall::balances(balances::Call::transfer { to, amount }),
+				Call::Claim { content } => {
+					RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: content })
+				},
+			};
+			let nonce = node.submit(from, nonce, call)?;
+			println!("Submitted transaction {} of {}", nonce, from.name());
+			println!("Transactions in the pool: {}", node.pool.len());
+		},
+		Command::ProduceBlock { out } => {
+			let mut node = Node::open(data_dir)?;
+			if node.pool.is_empty() {
+				println!("No transactions in the pool, producing an empty block");
+			}
+			let block = node.runtime.build_block(node.pool.ready(&node.runtime));
+			let receipt = node.import(block.clone())?;
+			print_block(&block, &receipt);
+			if let Some(path) = out {
+				std::fs::write(path, block.encode())?;
+			}
+		},
+		Command::ImportBlock { file } => {
+			let mut node = Node::open(data_dir)?;
+			let block: types::Block = codec::decode_all(&std::fs::read(file)?)?;
+			let receipt = node.import(block.clone())?;
+			print_block(&block, &receipt);
+		},
+		Command::Query(Query::Balance { account, prove }) => {
+			let node = Node::open(data_dir)?;
+			println!("{}", node.runtime.balances.balance(&account));
+			if prove {
+				let proof = node.prove(&node.runtime.balances.balance_key(&account))?;
+				let header = node.last_header()?;
+				let balance = balances::Pallet::<Runtime>::verify_balance(&header, &account, &proof);
+				print_proof(&header, &proof, balance.is_some());
+			}
+		},
+		Command::Query(Query::Claim { content, prove }) => {
+			let node = Node::open(data_dir)?;
+			match node.runtime.proof_of_existence.get_claim(&content) {
+				Some(owner) => println!("{:?}", owner),
+				None => println!("unclaimed"),
+			}
+			if prove {
+				let proof = node.prove(&node.runtime.proof_of_existence.claim_key(&content))?;
+				let header = node.last_header()?;
+				let owner = proof_of_existence::Pallet::<Runtime>::verify_claim(&header, &content, &proof);
+				print_proof(&header, &proof, owner.is_some());
+			}
+		},
+		Command::ExportState => {
+			let node = Node::open(data_dir)?;
+			let state = serde_json::to_string_pretty(&node.runtime).expect("state can be written as json");
+			println!("{}", state);
+		},
+		Command::Metadata => {
+			let metadata = serde_json::to_string_pretty(&Runtime::metadata()).expect("metadata can be written as json");
+			println!("{}", metadata);
+		},
+	}
+	Ok(())
+}
+
+// The chain spec is kept next to the store, so every command starts the same chain as `init`.
+const CHAIN_SPEC: &str = "chain_spec.json";
+
+// Start the chain described by `spec` in `data_dir`, which must not have a chain yet.
+fn init(data_dir: &Path, spec: ChainSpec) -> Result<Runtime, Error> {
+	let store = Store::open(data_dir)?;
+	let spec_path = data_dir.join(CHAIN_SPEC);
+	if spec_path.exists() || store.state()?.is_some() {
+		return Err(Error::AlreadyInitialized);
+	}
+	// The spec is written first: if the node stops before the genesis state is saved, the next
+	// command saves it.
+	spec.save(&spec_path)?;
+	Ok(crate::open_chain(spec, &store)?)
+}
+
+// A node started from a data directory: the chain, the store it is saved in, and the transactions
+// waiting for a block.
+struct Node {
+	runtime: Runtime,
+	store: Store,
+	pool: TransactionPool,
+}
+
+impl Node {
+	fn open(data_dir: &Path) -> Result<Self, Error> {
+		let spec = match ChainSpec::load(&data_dir.join(CHAIN_SPEC)) {
+			Err(chain_spec::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+				return Err(Error::NotInitialized)
+			},
+			spec => spec?,
+		};
+		let store = Store::open(data_dir)?;
+		let runtime = crate::open_chain(spec, &store)?;
+		// Transactions which are not valid anymore are dropped.
+		let mut pool = TransactionPool::new();
+		for extrinsic in store.pool()? {
+			let _ = pool.submit(&runtime, extrinsic);
+		}
+		Ok(Self { runtime, store, pool })
+	}
+
+	// Sign `call` by `from`, and add it to the pool. Returns the nonce of the transaction.
+	fn submit(
+		&mut self,
+		from: Keyring,
+		nonce: Option<types::Nonce>,
+		call: RuntimeCall,
+	) -> Result<types::Nonce, Error> {
+		let nonce = nonce.unwrap_or_else(|| self.pool.next_nonce(&self.runtime, &from.public()));
+		let extrinsic =
+			support::Extrinsic::new_signed(&from.pair(), nonce, call, self.runtime.system.genesis_hash());
+		self.pool.submit(&self.runtime, extrinsic).map_err(Error::InvalidTransaction)?;
+		self.store.save_pool(&self.pool.transactions())?;
+		Ok(nonce)
+	}
+
+	// Import `block`, and drop the transactions of the pool it made invalid.
+	fn import(&mut self, block: types::Block) -> Result<types::BlockReceipt, Error> {
+		let receipt = crate::import_block(&mut self.runtime, &self.store, block)?;
+		self.pool.prune(&self.runtime);
+		self.store.save_pool(&self.pool.transactions())?;
+		Ok(receipt)
+	}
+
+	fn prove(&self, key: &[u8]) -> Result<crate::merkle::Proof, Error> {
+		self.runtime.prove(key).ok_or(Error::NothingToProve)
+	}
+
+	// The header of the last imported block, or the genesis header if none was imported yet.
+	fn last_header(&self) -> Result<types::Header, Error> {
+		let number = self.runtime.system.block_number();
+		Ok(match self.store.block::<types::Block>(number.into())? {
+			Some(block) => block.header,
+			None => self.runtime.system.genesis_header().clone(),
+		})
+	}
+}
+
+// Print the outcome of an imported block: the result, weight and events of each extrinsic.
+fn print_block(block: &types::Block, receipt: &types::BlockReceipt) {
+	println!("Imported block {} {}", block.header.block_number, hex::encode(&block.header.hash()));
+	for extrinsic in &receipt.extrinsics {
+		let result = match extrinsic.result {
+			Ok(()) => "ok".to_string(),
+			Err(e) => e.to_string(),
+		};
+		println!(
+			"Extrinsic {} from {:?}: {} (weight {})",
+			extrinsic.index, extrinsic.caller, result, extrinsic.weight
+		);
+		for event in &extrinsic.events {
+			println!("\t{:?}", event);
+		}
+	}
+}
+
+fn print_proof(header: &types::Header, proof: &crate::merkle::Proof, valid: bool) {
+	println!("Proof: {}", hex::encode(&proof.encode()));
+	println!(
+		"Checked against the state root of block {}: {}",
+		header.block_number,
+		if valid { "valid" } else { "invalid" }
+	);
+}
+
+fn parse_keyring(name: &str) -> Result<Keyring, String> {
+	Keyring::from_name(name).ok_or_else(|| format!("unknown development account `{}`", name))
+}
+
+// An account is either a development account, by name, or a hex public key.
+fn parse_account(account: &str) -> Result<types::AccountId, String> {
+	if let Some(keyring) = Keyring::from_name(account) {
+		return Ok(keyring.public());
+	}
+	hex::decode(account)
+		.map(PublicKey)
+		.map_err(|e| format!("not a development account nor a public key: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use clap::{CommandFactory, Parser};
+
+	use super::{run, Cli, Error, Node};
+	use crate::store::test_dir;
+	use crate::Keyring;
+
+	// Run the command `args` on the data directory `dir`.
+	fn node(dir: &std::path::Path, args: &[&str]) -> Result<(), Error> {
+		let dir = dir.to_str().unwrap();
+		run(Cli::try_parse_from(["node", "--data-dir", dir].iter().chain(args)).unwrap())
+	}
+
+	#[test]
+	fn valid_cli() {
+		Cli::command().debug_assert();
+		assert!(Cli::try_parse_from(["node", "submit", "--from", "dave", "claim", "doc"]).is_err());
+		assert!(Cli::try_parse_from(["node", "query", "balance", "0x1234"]).is_err());
+	}
+
+	#[test]
+	fn produce_and_import_blocks() {
+		let producer = test_dir("cli-producer");
+		let importer = test_dir("cli-importer");
+		assert!(matches!(node(&producer, &["produce-block"]), Err(Error::NotInitialized)));
+		node(&producer, &["init"]).unwrap();
+		node(&importer, &["init"]).unwrap();
+		assert!(matches!(node(&producer, &["init"]), Err(Error::AlreadyInitialized)));
+		// Before the first block, proofs are checked against the genesis header.
+		let genesis = Node::open(&importer).unwrap();
+		assert_eq!(genesis.last_header().unwrap().hash(), genesis.runtime.system.genesis_hash());
+
+		node(&producer, &["submit", "--from", "alice", "transfer", "--to", "bob", "--amount", "30"]).unwrap();
+		node(&producer, &["submit", "--from", "alice", "claim", "my_document"]).unwrap();
+		// Bob has no tokens to pay the fee with.
+		assert!(matches!(
+			node(&producer, &["submit", "--from", "bob", "claim", "my_document"]),
+			Err(Error::InvalidTransaction(_))
+		));
+		let block = producer.join("block_1");
+		node(&producer, &["produce-block", "--out", block.to_str().unwrap()]).unwrap();
+		node(&importer, &["import-block", block.to_str().unwrap()]).unwrap();
+		assert!(matches!(node(&importer, &["import-block", block.to_str().unwrap()]), Err(Error::InvalidBlock(_))));
+		node(&importer, &["query", "balance", "bob", "--prove"]).unwrap();
+		node(&importer, &["query", "claim", "my_document", "--prove"]).unwrap();
+		assert!(matches!(node(&importer, &["query", "claim", "other", "--prove"]), Err(Error::NothingToProve)));
+
+		let produced = Node::open(&producer).unwrap();
+		let imported = Node::open(&importer).unwrap();
+		assert!(produced.pool.is_empty());
+		assert_eq!(produced.runtime.system.block_number(), 1);
+		assert_eq!(imported.runtime.storage(), produced.runtime.storage());
+		assert_eq!(imported.runtime.balances.balance(&Keyring::Bob.public()), 30);
+		assert_eq!(
+			imported.runtime.proof_of_existence.get_claim(&"my_document".to_string()),
+			Some(Keyring::Alice.public())
+		);
+		std::fs::remove_dir_all(&producer).unwrap();
+		std::fs::remove_dir_all(&importer).unwrap();
+	}
+}
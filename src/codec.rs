@@ -0,0 +1,373 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+/// A compact, deterministic binary encoding for everything that leaves the runtime: blocks,
+/// extrinsics and calls.
+///
+/// - Integers are fixed width, little-endian.
+/// - Lengths of strings and collections are prefixed as LEB128 variable-length integers.
+/// - Fixed-size byte arrays, like hashes and keys, are written as-is.
+/// - Enums, like `Call` and `RuntimeCall`, are a one byte variant index followed by the fields of
+///   the variant.
+///
+/// Two equal values always have the same encoding, so encoded values can be hashed and signed.
+pub trait Encode {
+	/// Append the encoding of `self` to `dest`.
+	fn encode_to(&self, dest: &mut Vec<u8>);
+
+	/// Get the encoding of `self`.
+	fn encode(&self) -> Vec<u8> {
+		let mut dest = Vec::new();
+		self.encode_to(&mut dest);
+		dest
+	}
+}
+
+/// The reverse of `Encode`.
+pub trait Decode: Sized {
+	/// Decode a value from the start of `input`, advancing `input` past the bytes which were read.
+	fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+/// Decode a value which must use all of `bytes`.
+pub fn decode_all<T: Decode>(mut bytes: &[u8]) -> Result<T, Error> {
+	let value = T::decode(&mut bytes)?;
+	if !bytes.is_empty() {
+		return Err(Error::TrailingBytes);
+	}
+	Ok(value)
+}
+
+/// The reasons decoding can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The input ended before the value was complete.
+	UnexpectedEnd,
+	/// There are bytes left after the value.
+	TrailingBytes,
+	/// The variant index of an enum does not exist.
+	InvalidVariant(u8),
+	/// A length prefix is too large or not minimally encoded.
+	InvalidLength,
+	/// A string is not valid UTF-8.
+	InvalidUtf8,
+	/// A boolean is neither 0 nor 1.
+	InvalidBool,
+	/// The keys of a map are not in strictly increasing order, as they are encoded.
+	UnorderedKeys,
+	/// A recursive value, like a call holding other calls, is nested too deeply.
+	TooDeep,
+}
+
+impl core::fmt::Display for Error {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Error::UnexpectedEnd => write!(f, "unexpected end of input"),
+			Error::TrailingBytes => write!(f, "trailing bytes after the value"),
+			Error::InvalidVariant(index) => write!(f, "invalid variant index {}", index),
+			Error::InvalidLength => write!(f, "invalid length prefix"),
+			Error::InvalidUtf8 => write!(f, "invalid utf-8 string"),
+			Error::InvalidBool => write!(f, "invalid boolean"),
+			Error::UnorderedKeys => write!(f, "map keys not in strictly increasing order"),
+			Error::TooDeep => write!(f, "value nested too deeply"),
+		}
+	}
+}
+
+thread_local! {
+	// How many values are being decoded through `decode_nested` on this thread, one inside the other.
+	static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Decode a value with `decode`, one level deeper than its caller. Recursive types, like calls
+/// holding other calls, decode through this, so an input nested more than `max_depth` levels
+/// deep returns `Error::TooDeep` instead of overflowing the stack.
+pub fn decode_nested<T>(max_depth: u32, decode: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+	let depth = DEPTH.with(|depth| depth.get());
+	if depth >= max_depth {
+		return Err(Error::TooDeep);
+	}
+	DEPTH.with(|d| d.set(depth + 1));
+	let result = decode();
+	DEPTH.with(|d| d.set(depth));
+	result
+}
+
+/// Read the next `len` bytes of `input`.
+pub fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+	if input.len() < len {
+		return Err(Error::UnexpectedEnd);
+	}
+	let (bytes, rest) = input.split_at(len);
+	*input = rest;
+	Ok(bytes)
+}
+
+/// Write the length prefix of a string or collection.
+pub fn encode_len(len: usize, dest: &mut Vec<u8>) {
+	let mut len = len as u64;
+	loop {
+		let byte = (len & 0x7f) as u8;
+		len >>= 7;
+		if len == 0 {
+			dest.push(byte);
+			return;
+		}
+		dest.push(byte | 0x80);
+	}
+}
+
+/// Read the length prefix of a string or collection.
+pub fn decode_len(input: &mut &[u8]) -> Result<usize, Error> {
+	let mut len: u64 = 0;
+	for shift in (0..64).step_by(7) {
+		let byte = u8::decode(input)?;
+		// Only the last byte can be zero, otherwise the same length has several encodings.
+		if byte == 0 && shift > 0 {
+			return Err(Error::InvalidLength);
+		}
+		let bits = (byte & 0x7f) as u64;
+		if shift == 63 && bits > 1 {
+			return Err(Error::InvalidLength);
+		}
+		len |= bits << shift;
+		if byte & 0x80 == 0 {
+			return usize::try_from(len).map_err(|_| Error::InvalidLength);
+		}
+	}
+	Err(Error::InvalidLength)
+}
+
+macro_rules! impl_codec_for_int {
+	($($int:ty),*) => {
+		$(
+			impl Encode for $int {
+				fn encode_to(&self, dest: &mut Vec<u8>) {
+					dest.extend_from_slice(&self.to_le_bytes());
+				}
+			}
+
+			impl Decode for $int {
+				fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+					let bytes = read_bytes(input, core::mem::size_of::<$int>())?;
+					Ok(<$int>::from_le_bytes(bytes.try_into().expect("read the size of the int")))
+				}
+			}
+		)*
+	};
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, u128);
+
+impl Encode for bool {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.push(*self as u8);
+	}
+}
+
+impl Decode for bool {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(Error::InvalidBool),
+		}
+	}
+}
+
+impl<const N: usize> Encode for [u8; N] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.extend_from_slice(self);
+	}
+}
+
+impl<const N: usize> Decode for [u8; N] {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(read_bytes(input, N)?.try_into().expect("read N bytes"))
+	}
+}
+
+impl Encode for str {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		dest.extend_from_slice(self.as_bytes());
+	}
+}
+
+impl Encode for String {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_str().encode_to(dest);
+	}
+}
+
+impl Decode for String {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		let bytes = read_bytes(input, len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidUtf8)
+	}
+}
+
+impl<T: Encode> Encode for [T] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Encode> Encode for Vec<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_slice().encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Vec<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		// Every item takes at least one byte, so a bogus length cannot make us allocate more than
+		// the input.
+		let mut items = Vec::with_capacity(len.min(input.len()));
+		for _ in 0..len {
+			items.push(T::decode(input)?);
+		}
+		Ok(items)
+	}
+}
+
+impl<T: Encode> Encode for Option<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			None => dest.push(0),
+			Some(value) => {
+				dest.push(1);
+				value.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl<T: Decode> Decode for Option<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(None),
+			1 => Ok(Some(T::decode(input)?)),
+			index => Err(Error::InvalidVariant(index)),
+		}
+	}
+}
+
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		for (key, value) in self {
+			key.encode_to(dest);
+			value.encode_to(dest);
+		}
+	}
+}
+
+// A map has a single encoding: its keys are encoded in order, without duplicates.
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		let mut map = BTreeMap::new();
+		for _ in 0..len {
+			let key = K::decode(input)?;
+			if map.last_key_value().is_some_and(|(last, _)| *last >= key) {
+				return Err(Error::UnorderedKeys)
+			}
+			let value = V::decode(input)?;
+			map.insert(key, value);
+		}
+		Ok(map)
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+// A boxed value is encoded as the value itself.
+impl<T: Encode + ?Sized> Encode for Box<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Box<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		T::decode(input).map(Box::new)
+	}
+}
+
+macro_rules! impl_codec_for_tuple {
+	($($name:ident),*) => {
+		impl<$($name: Encode),*> Encode for ($($name,)*) {
+			#[allow(non_snake_case)]
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				let ($($name,)*) = self;
+				$( $name.encode_to(dest); )*
+			}
+		}
+
+		impl<$($name: Decode),*> Decode for ($($name,)*) {
+			fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+				Ok(($($name::decode(input)?,)*))
+			}
+		}
+	};
+}
+
+impl_codec_for_tuple!(A, B);
+impl_codec_for_tuple!(A, B, C);
+
+#[cfg(test)]
+mod tests {
+	use super::{decode_all, Decode, Encode, Error};
+	use std::collections::BTreeMap;
+
+	fn round_trip<T: Encode + Decode + PartialEq + core::fmt::Debug>(value: T) {
+		assert_eq!(decode_all::<T>(&value.encode()), Ok(value));
+	}
+
+	#[test]
+	fn encode_primitives() {
+		assert_eq!(1u32.encode(), vec![1, 0, 0, 0]);
+		assert_eq!(true.encode(), vec![1]);
+		assert_eq!([7u8; 3].encode(), vec![7, 7, 7]);
+		assert_eq!("ab".to_string().encode(), vec![2, b'a', b'b']);
+		assert_eq!(vec![1u8; 200].encode()[..2], [0xc8, 0x01]);
+		assert_eq!(Some(5u8).encode(), vec![1, 5]);
+	}
+
+	#[test]
+	fn round_trip_values() {
+		round_trip(u128::MAX);
+		round_trip(String::from("my_document"));
+		round_trip(vec![vec![1u16, 2], vec![]]);
+		round_trip(vec![0u8; 1000]);
+		round_trip(Some([3u8; 32]));
+		round_trip((1u8, String::from("x"), false));
+		round_trip(BTreeMap::from([(1u32, String::from("a")), (2, String::from("b"))]));
+	}
+
+	#[test]
+	fn reject_invalid_input() {
+		assert_eq!(decode_all::<u32>(&[1, 0, 0]), Err(Error::UnexpectedEnd));
+		assert_eq!(decode_all::<u8>(&[1, 0]), Err(Error::TrailingBytes));
+		assert_eq!(decode_all::<bool>(&[2]), Err(Error::InvalidBool));
+		assert_eq!(decode_all::<String>(&[1, 0xff]), Err(Error::InvalidUtf8));
+		assert_eq!(decode_all::<Option<u8>>(&[2]), Err(Error::InvalidVariant(2)));
+		// A length of 1 which is not minimally encoded.
+		assert_eq!(decode_all::<Vec<u8>>(&[0x81, 0x00, 1]), Err(Error::InvalidLength));
+		assert_eq!(decode_all::<Vec<u8>>(&[5, 1, 2]), Err(Error::UnexpectedEnd));
+		assert_eq!(u32::decode(&mut &[][..]), Err(Error::UnexpectedEnd));
+		// The same map, with its keys out of order or repeated.
+		assert_eq!(decode_all::<BTreeMap<u8, bool>>(&[2, 2, 1, 1, 0]), Err(Error::UnorderedKeys));
+		assert_eq!(decode_all::<BTreeMap<u8, bool>>(&[2, 1, 1, 1, 0]), Err(Error::UnorderedKeys));
+	}
+}
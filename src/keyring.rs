@@ -0,0 +1,60 @@
+use crate::support::{self, Pair, PublicKey};
+
+/// Well-known development accounts with deterministic keys, so local scenarios and tests can sign
+/// extrinsics without managing key files. Everyone can derive these keys: never give them any
+/// value on a shared network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyring {
+	Alice,
+	Bob,
+	Charlie,
+}
+
+impl Keyring {
+	/// Every development account.
+	pub const ALL: [Keyring; 3] = [Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+
+	/// The account derived from `name`, ignoring case, like `alice`.
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|keyring| keyring.name().eq_ignore_ascii_case(name))
+	}
+
+	/// The name the key is derived from.
+	pub fn name(self) -> &'static str {
+		match self {
+			Keyring::Alice => "Alice",
+			Keyring::Bob => "Bob",
+			Keyring::Charlie => "Charlie",
+		}
+	}
+
+	/// The key pair of this account. Its seed is the hash of `//` followed by the name.
+	pub fn pair(self) -> Pair {
+		Pair::from_seed(&support::hash(format!("//{}", self.name()).as_bytes()))
+	}
+
+	/// The public key of this account, which is its account id.
+	pub fn public(self) -> PublicKey {
+		self.pair().public()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Keyring;
+
+	#[test]
+	fn keys_are_deterministic_and_distinct() {
+		assert_eq!(Keyring::Alice.public(), Keyring::Alice.pair().public());
+		assert_ne!(Keyring::Alice.public(), Keyring::Bob.public());
+		assert_ne!(Keyring::Bob.public(), Keyring::Charlie.public());
+		assert_eq!(Keyring::from_name("alice"), Some(Keyring::Alice));
+		assert_eq!(Keyring::from_name("Charlie"), Some(Keyring::Charlie));
+		assert_eq!(Keyring::from_name("dave"), None);
+
+		let message = b"hello";
+		let signature = Keyring::Alice.pair().sign(message);
+		assert!(Keyring::Alice.public().verify(message, &signature));
+		assert!(!Keyring::Bob.public().verify(message, &signature));
+	}
+}
@@ -1,27 +1,47 @@
+use clap::Parser;
+use keyring::Keyring;
 use support::Dispatch;
 
 mod balances;
+mod chain_spec;
+mod cli;
+mod codec;
+mod keyring;
+mod merkle;
+mod metadata;
 mod system;
 mod support;
 mod proof_of_existence;
+mod storage;
+mod store;
+mod sudo;
+mod trace;
+mod transaction_pool;
 
 mod types {
     use crate::support;
 
-    pub type AccountId = String;
+    pub type AccountId = support::PublicKey;
     pub type Balance = u128;
     pub type BlockNumber = u32;
     pub type Nonce = u32;
-    pub type Extrinsic = support::Extrinsic<AccountId, crate::RuntimeCall>;
+    pub type Extrinsic = support::Extrinsic<AccountId, Nonce, crate::RuntimeCall>;
     pub type Header = support::Header<BlockNumber>;
     pub type Block = support::Block<Header, Extrinsic>;
-    pub type Content = &'static str;
+    pub type BlockReceipt = support::BlockReceipt<AccountId, crate::RuntimeEvent>;
+    pub type ExtrinsicReceipt = support::ExtrinsicReceipt<AccountId, crate::RuntimeEvent>;
+    pub type Content = String;
 }
 
 impl system::Config for Runtime {
     type AccountId = types::AccountId;
     type BlockNumber = types::BlockNumber;
     type Nonce = types::Nonce;
+    type Backend = storage::MemoryBackend;
+    type RuntimeEvent = RuntimeEvent;
+    const MAX_BLOCK_WEIGHT: support::Weight = 100_000;
+    const MAX_BLOCK_LENGTH: usize = 64 * 1024;
+    const MAX_CALL_DEPTH: u32 = 8;
 }
 
 impl balances::Config for Runtime {
@@ -32,64 +52,1147 @@ impl proof_of_existence::Config for Runtime {
     type Content = types::Content;
 }
 
+impl sudo::Config for Runtime {
+    type RuntimeCall = RuntimeCall;
+
+    fn call_weight(call: &RuntimeCall) -> support::Weight {
+        <Runtime as Dispatch>::weight(call)
+    }
+}
+
+// Fees are paid in the tokens of the balances pallet.
+impl support::TransactionPayment<types::AccountId> for Runtime {
+    type Balance = types::Balance;
+
+    // One token for every 1_000 units of weight, plus one token for every 100 bytes.
+    fn fee(weight: support::Weight, length: usize) -> types::Balance {
+        (weight / 1_000) as types::Balance + (length / 100) as types::Balance
+    }
+
+    fn can_pay_fee(&self, who: &types::AccountId, fee: types::Balance) -> support::DispatchResult {
+        self.balances.can_pay_fee(who, fee)
+    }
+
+    fn withdraw_fee(&mut self, who: &types::AccountId, fee: types::Balance) -> support::DispatchResult {
+        self.balances.withdraw_fee(who, fee)
+    }
+}
+
 // This is our main Runtime.
 // It accumulates all of the different pallets we want to use.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 #[macros::runtime]
 pub struct Runtime {
     system: system::Pallet<Runtime>,
     balances: balances::Pallet<Runtime>,
     proof_of_existence: proof_of_existence::Pallet<Runtime>,
+    sudo: sudo::Pallet<Runtime>,
 }
 
+// Start the chain described by `spec` from the state saved in `store`, or from its genesis block
+// if nothing was saved yet.
+fn open_chain(spec: chain_spec::ChainSpec, store: &store::Store) -> Result<Runtime, store::Error> {
+    let mut runtime = Runtime::from_genesis(spec.genesis);
+    match store.state()? {
+        Some(state) => {
+            let genesis_hash = runtime.system.genesis_hash();
+            runtime.restore_storage(&state)?;
+            if runtime.system.genesis_hash() != genesis_hash {
+                return Err(store::Error::GenesisMismatch);
+            }
+            // The next block is built on the last imported block, or on the genesis block if none
+            // was imported yet.
+            let last_block: Option<types::Block> = store.block(runtime.system.block_number().into())?;
+            let parent_hash = last_block.map_or(genesis_hash, |block| block.header.hash());
+            runtime.system.set_parent_hash(parent_hash);
+        },
+        None => store.save_state(&runtime.storage())?,
+    }
+    Ok(runtime)
+}
+
+// Execute a block on top of the current state, and save it along with the resulting state.
+fn import_block(
+    runtime: &mut Runtime,
+    store: &store::Store,
+    block: types::Block,
+) -> Result<types::BlockReceipt, cli::Error> {
+    let number = block.header.block_number;
+    let receipt = runtime.execute_block(block.clone()).map_err(cli::Error::InvalidBlock)?;
+    store.import_block(number.into(), &block, &runtime.storage())?;
+    Ok(receipt)
+}
+
+fn exit_on_error<T, E: core::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    })
+}
+
+// Run the command given on the command line, see `cli::Cli`.
 fn main() {
-	let mut runtime = Runtime::new();
-
-    // initialize the runtime
-    let alice = "alice".to_string();
-    let bob: String = "bob".to_string();
-    let charlie = "charlie".to_string();
-
-    runtime.balances.set_balance(&alice, 100);
-
-    let block_1 = types::Block {
-        header: support::Header { block_number: 1 },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer { to: bob.clone(), amount: 30 })
-            },
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::balances(balances::Call::transfer { to: charlie.clone(), amount: 20 }),
-            },
-        ],
-    };
-
-    runtime.execute_block(block_1).expect("wrong block execution");
-
-    /*
-		TODO:
-		Create new block(s) which execute extrinsics for the new `ProofOfExistence` pallet.
-			- Make sure to set the block number correctly.
-			- Feel free to allow some extrinsics to fail, and see the errors appear.
-	*/
-    let block_2 = types::Block {
-        header: support::Header { block_number: 2 },
-        extrinsics: vec![
-            support::Extrinsic {
-                caller: alice.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "my_document" })
-            },
-            support::Extrinsic {
-                caller: bob.clone(),
-                call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "bob's document" }),
-            },
-        ],
-    };
-
-    runtime.execute_block(block_2).expect("wrong block execution");
-    
-
-    println!("Runtime: {:#?}", runtime);
+    exit_on_error(cli::run(cli::Cli::parse()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{balances, codec, proof_of_existence, sudo, support, system, types};
+    use super::{Dispatch, GenesisConfig, Keyring, Runtime, RuntimeCall, RuntimeEvent};
+    use codec::Encode;
+
+    // A runtime where alice has 100 tokens, sealed as the genesis of the chain.
+    fn new_runtime() -> Runtime {
+        Runtime::from_genesis(GenesisConfig {
+            balances: balances::GenesisConfig { balances: vec![(Keyring::Alice.public(), 100)] },
+            ..Default::default()
+        })
+    }
+
+    fn transfer(
+        runtime: &Runtime,
+        from: Keyring,
+        nonce: u32,
+        to: Keyring,
+        amount: u128,
+    ) -> types::Extrinsic {
+        support::Extrinsic::new_signed(
+            &from.pair(),
+            nonce,
+            RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount }),
+            runtime.system.genesis_hash(),
+        )
+    }
+
+    // The receipt of a successful transfer at `index` in its block. A transfer weighs 1_000 and
+    // is 150 bytes long, so its fee is 2.
+    fn transfer_receipt(index: u32, from: Keyring, to: Keyring, amount: u128) -> types::ExtrinsicReceipt {
+        support::ExtrinsicReceipt {
+            index,
+            caller: from.public(),
+            result: Ok(()),
+            weight: 1_000,
+            events: vec![
+                RuntimeEvent::balances(balances::Event::FeePaid { who: from.public(), amount: 2 }),
+                RuntimeEvent::balances(balances::Event::Transfer { from: from.public(), to: to.public(), amount }),
+            ],
+        }
+    }
+
+    #[test]
+    fn execute_sealed_blocks() {
+        let mut runtime = new_runtime();
+        assert_eq!(runtime.system.parent_hash(), runtime.system.genesis_hash());
+
+        let block_1 = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30)]);
+        let block_1_hash = block_1.header.hash();
+        assert_eq!(
+            runtime.execute_block(block_1),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30)] })
+        );
+        assert_eq!(runtime.system.parent_hash(), block_1_hash);
+
+        let block_2 = runtime.build_block(vec![transfer(&runtime, Keyring::Bob, 0, Keyring::Charlie, 10)]);
+        assert_eq!(block_2.header.parent_hash, block_1_hash);
+        assert_eq!(
+            runtime.execute_block(block_2),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Bob, Keyring::Charlie, 10)] })
+        );
+        assert_eq!(runtime.balances.balance(&Keyring::Charlie.public()), 10);
+    }
+
+    #[test]
+    fn build_blocks_for_other_nodes() {
+        let mut runtime = new_runtime();
+        let mut forged = transfer(&runtime, Keyring::Bob, 0, Keyring::Bob, 30);
+        forged.caller = Keyring::Alice.public();
+
+        let block = runtime.build_block(vec![
+            transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30),
+            forged,
+            transfer(&runtime, Keyring::Alice, 5, Keyring::Bob, 30),
+            transfer(&runtime, Keyring::Charlie, 0, Keyring::Bob, 0),
+            transfer(&runtime, Keyring::Alice, 1, Keyring::Charlie, 10),
+        ]);
+        let nonces = block.extrinsics.iter().map(|extrinsic| extrinsic.nonce).collect::<Vec<_>>();
+        assert_eq!(nonces, vec![0, 1]);
+        assert_eq!(block.header.block_number, 1);
+        // Building a block does not change the state of the author.
+        assert_eq!(runtime.system.block_number(), 0);
+
+        let mut other = new_runtime();
+        assert_eq!(
+            other.execute_block(block),
+            Ok(types::BlockReceipt {
+                extrinsics: vec![
+                    transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30),
+                    transfer_receipt(1, Keyring::Alice, Keyring::Charlie, 10),
+                ],
+            })
+        );
+        assert_eq!(other.balances.balance(&Keyring::Charlie.public()), 10);
+    }
+
+    #[test]
+    fn execute_blocks_with_failed_calls() {
+        let mut runtime = new_runtime();
+        let (alice, bob, charlie) = (Keyring::Alice.public(), Keyring::Bob.public(), Keyring::Charlie.public());
+
+        // A failed call is still part of the block: its fee is kept, but its transfer is reverted.
+        let block = runtime.build_block(vec![
+            transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30),
+            transfer(&runtime, Keyring::Alice, 1, Keyring::Bob, 100),
+            transfer(&runtime, Keyring::Bob, 0, Keyring::Charlie, 10),
+            transfer(&runtime, Keyring::Charlie, 0, Keyring::Alice, 20),
+        ]);
+        assert_eq!(block.extrinsics.len(), 4);
+        let failed_transfer = |index, caller: Keyring| support::ExtrinsicReceipt {
+            index,
+            caller: caller.public(),
+            result: Err(balances::Error::InsufficientBalance.into()),
+            weight: 1_000,
+            events: vec![RuntimeEvent::balances(balances::Event::FeePaid { who: caller.public(), amount: 2 })],
+        };
+        let receipt = runtime.execute_block(block).unwrap();
+        assert_eq!(
+            receipt,
+            types::BlockReceipt {
+                extrinsics: vec![
+                    transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30),
+                    failed_transfer(1, Keyring::Alice),
+                    transfer_receipt(2, Keyring::Bob, Keyring::Charlie, 10),
+                    failed_transfer(3, Keyring::Charlie),
+                ],
+            }
+        );
+
+        assert_eq!(runtime.balances.balance(&alice), 66);
+        assert_eq!(runtime.balances.balance(&bob), 18);
+        assert_eq!(runtime.balances.balance(&charlie), 8);
+        assert_eq!(runtime.system.get_nonce(&alice), 2);
+        assert_eq!(runtime.system.get_nonce(&charlie), 1);
+        assert_eq!(runtime.system.block_weight(), 4_000);
+
+        // The events of the block are those of the receipts, with their extrinsic.
+        let records = receipt
+            .extrinsics
+            .iter()
+            .flat_map(|extrinsic| {
+                extrinsic.events.iter().map(|event| system::EventRecord {
+                    phase: system::Phase::ApplyExtrinsic(extrinsic.index),
+                    event: event.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(runtime.system.events(), records.as_slice());
+    }
+
+    #[test]
+    fn build_blocks_up_to_the_limits() {
+        let mut runtime = new_runtime();
+        runtime.balances.set_balance(&Keyring::Alice.public(), 100_000);
+
+        let transfers = (0..150)
+            .map(|nonce| transfer(&runtime, Keyring::Alice, nonce, Keyring::Bob, 1))
+            .collect();
+        let block = runtime.build_block(transfers);
+        assert_eq!(block.extrinsics.len(), 100);
+        let receipts = (0..100).map(|index| transfer_receipt(index, Keyring::Alice, Keyring::Bob, 1)).collect();
+        assert_eq!(runtime.execute_block(block), Ok(types::BlockReceipt { extrinsics: receipts }));
+
+        // Each claim is 30 KiB, so only two of them fit in a block.
+        let genesis_hash = runtime.system.genesis_hash();
+        let claim = |index: u32| {
+            support::Extrinsic::new_signed(
+                &Keyring::Alice.pair(),
+                100 + index,
+                RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                    claim: index.to_string().repeat(30 * 1024),
+                }),
+                genesis_hash,
+            )
+        };
+        let block = runtime.build_block((0..3).map(claim).collect());
+        assert_eq!(block.extrinsics.len(), 2);
+
+        let mut too_long = block.clone();
+        too_long.extrinsics.push(claim(2));
+        too_long.header.extrinsics_root = support::extrinsics_root(&too_long.extrinsics);
+        assert_eq!(
+            runtime.execute_block(too_long),
+            Err(system::Error::BlockLengthExceeded.into())
+        );
+
+        // A claim weighs 1_500, and pays one token for every 100 bytes on top of that.
+        let claim_receipt = |index: u32| {
+            let fee = 1 + (claim(index).encode().len() / 100) as u128;
+            support::ExtrinsicReceipt {
+                index,
+                caller: Keyring::Alice.public(),
+                result: Ok(()),
+                weight: 1_500,
+                events: vec![
+                    RuntimeEvent::balances(balances::Event::FeePaid { who: Keyring::Alice.public(), amount: fee }),
+                    RuntimeEvent::proof_of_existence(proof_of_existence::Event::ClaimCreated {
+                        owner: Keyring::Alice.public(),
+                        claim: index.to_string().repeat(30 * 1024),
+                    }),
+                ],
+            }
+        };
+        assert_eq!(
+            runtime.execute_block(block),
+            Ok(types::BlockReceipt { extrinsics: vec![claim_receipt(0), claim_receipt(1)] })
+        );
+    }
+
+    #[test]
+    fn reject_invalid_headers() {
+        let mut runtime = new_runtime();
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30)]);
+        let storage = runtime.storage();
+
+        // A block which is not valid changes nothing, even when it fails after some extrinsics.
+        let mut wrong_number = block.clone();
+        wrong_number.header.block_number = 2;
+        assert_eq!(
+            runtime.execute_block(wrong_number),
+            Err(system::Error::InvalidBlockNumber.into())
+        );
+        assert_eq!(runtime.system.block_number(), 0);
+
+        let mut wrong_parent = block.clone();
+        wrong_parent.header.parent_hash = [1; 32];
+        assert_eq!(
+            runtime.execute_block(wrong_parent),
+            Err(system::Error::InvalidParentHash.into())
+        );
+
+        let mut wrong_extrinsics = block.clone();
+        wrong_extrinsics.extrinsics.push(transfer(&runtime, Keyring::Alice, 1, Keyring::Charlie, 10));
+        assert_eq!(
+            runtime.execute_block(wrong_extrinsics),
+            Err(system::Error::BadExtrinsicsRoot.into())
+        );
+
+        let mut wrong_state = block.clone();
+        wrong_state.header.state_root = [1; 32];
+        assert_eq!(
+            runtime.execute_block(wrong_state),
+            Err(system::Error::BadStateRoot.into())
+        );
+        assert_eq!(runtime.storage(), storage);
+        assert!(runtime.system.events().is_empty());
+
+        assert_eq!(
+            runtime.execute_block(block),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30)] })
+        );
+        assert_eq!(runtime.balances.balance(&Keyring::Bob.public()), 30);
+    }
+
+    #[test]
+    fn reject_blocks_with_invalid_extrinsics() {
+        let mut runtime = new_runtime();
+        let alice = Keyring::Alice.public();
+        let block_1 = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30)]);
+        assert_eq!(
+            runtime.execute_block(block_1),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30)] })
+        );
+        let storage = runtime.storage();
+        let events = runtime.system.events().to_vec();
+        let balances = [Keyring::Alice, Keyring::Bob, Keyring::Charlie]
+            .map(|keyring| runtime.balances.balance(&keyring.public()));
+
+        // The first two extrinsics are valid, but the third one skips a nonce, so the whole block
+        // fails after they have been applied.
+        let mut block_2 = runtime.build_block(vec![
+            transfer(&runtime, Keyring::Alice, 1, Keyring::Bob, 10),
+            transfer(&runtime, Keyring::Alice, 2, Keyring::Charlie, 10),
+        ]);
+        assert_eq!(block_2.extrinsics.len(), 2);
+        block_2.extrinsics.push(transfer(&runtime, Keyring::Alice, 5, Keyring::Charlie, 10));
+        block_2.header.extrinsics_root = support::extrinsics_root(&block_2.extrinsics);
+        assert_eq!(runtime.execute_block(block_2), Err(system::Error::FutureNonce.into()));
+
+        // None of the balances, nonces or events of the first two extrinsics are kept.
+        let balances_after = [Keyring::Alice, Keyring::Bob, Keyring::Charlie]
+            .map(|keyring| runtime.balances.balance(&keyring.public()));
+        assert_eq!(balances_after, balances);
+        assert_eq!(runtime.system.get_nonce(&alice), 1);
+        assert_eq!(runtime.system.events(), events.as_slice());
+        assert_eq!(runtime.system.block_number(), 1);
+        assert_eq!(runtime.storage(), storage);
+    }
+
+    #[test]
+    fn reject_replayed_extrinsics() {
+        let mut runtime = new_runtime();
+        let alice = Keyring::Alice.public();
+
+        let block_1 = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30)]);
+        assert_eq!(
+            runtime.execute_block(block_1),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Alice, Keyring::Bob, 30)] })
+        );
+        assert_eq!(runtime.system.get_nonce(&alice), 1);
+
+        // The same extrinsic again, in a block which is otherwise valid.
+        let mut block_2 = runtime.build_block(vec![]);
+        block_2.extrinsics.push(transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30));
+        block_2.header.extrinsics_root = support::extrinsics_root(&block_2.extrinsics);
+        assert_eq!(runtime.execute_block(block_2), Err(system::Error::StaleNonce.into()));
+
+        let future = transfer(&runtime, Keyring::Alice, 2, Keyring::Bob, 30);
+        assert_eq!(runtime.apply_extrinsic(0, future), Err(system::Error::FutureNonce.into()));
+        assert_eq!(runtime.system.get_nonce(&alice), 1);
+        assert_eq!(runtime.balances.balance(&Keyring::Bob.public()), 30);
+    }
+
+    #[test]
+    fn reject_bad_signatures() {
+        let mut runtime = new_runtime();
+
+        // Bob signs a transfer, but claims to be alice.
+        let mut forged = transfer(&runtime, Keyring::Bob, 0, Keyring::Bob, 30);
+        forged.caller = Keyring::Alice.public();
+        assert_eq!(runtime.apply_extrinsic(0, forged), Err(system::Error::BadSignature.into()));
+
+        // Alice signs a transfer for another chain.
+        let other_chain = support::Extrinsic::new_signed(
+            &Keyring::Alice.pair(),
+            0,
+            RuntimeCall::balances(balances::Call::transfer { to: Keyring::Bob.public(), amount: 30 }),
+            [1; 32],
+        );
+        assert_eq!(runtime.apply_extrinsic(0, other_chain), Err(system::Error::BadSignature.into()));
+
+        assert_eq!(runtime.system.get_nonce(&Keyring::Alice.public()), 0);
+        assert_eq!(runtime.balances.balance(&Keyring::Bob.public()), 0);
+    }
+
+    #[test]
+    fn charge_fees() {
+        let mut runtime = new_runtime();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+
+        // The transfer has a weight of 1_000 and is 150 bytes long.
+        let extrinsic = transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30);
+        assert_eq!(extrinsic.encode().len(), 150);
+        assert_eq!(runtime.apply_extrinsic(0, extrinsic).map(|receipt| receipt.result), Ok(Ok(())));
+        assert_eq!(runtime.balances.balance(&alice), 68);
+        assert_eq!(runtime.balances.balance(&bob), 30);
+
+        // The fee is kept when the call fails.
+        let too_much = transfer(&runtime, Keyring::Alice, 1, Keyring::Bob, 100);
+        assert_eq!(
+            runtime.apply_extrinsic(1, too_much),
+            Ok(support::ExtrinsicReceipt {
+                index: 1,
+                caller: alice,
+                result: Err(balances::Error::InsufficientBalance.into()),
+                weight: 1_000,
+                events: vec![RuntimeEvent::balances(balances::Event::FeePaid { who: alice, amount: 2 })],
+            })
+        );
+        assert_eq!(runtime.balances.balance(&alice), 66);
+        assert_eq!(runtime.system.get_nonce(&alice), 2);
+        assert_eq!(
+            runtime.system.events().last().map(|record| &record.event),
+            Some(&RuntimeEvent::balances(balances::Event::FeePaid { who: alice, amount: 2 }))
+        );
+
+        // Charlie cannot pay for anything, so the extrinsic is invalid and changes nothing.
+        let charlie = Keyring::Charlie.public();
+        let weight = runtime.system.block_weight();
+        assert_eq!(
+            runtime.apply_extrinsic(2, transfer(&runtime, Keyring::Charlie, 0, Keyring::Bob, 0)),
+            Err(balances::Error::CannotPayFee.into())
+        );
+        assert_eq!(runtime.system.get_nonce(&charlie), 0);
+        assert_eq!(runtime.system.block_weight(), weight);
+    }
+
+    #[test]
+    fn reject_overweight_blocks() {
+        let mut runtime = new_runtime();
+        runtime.balances.set_balance(&Keyring::Alice.public(), 1_000);
+        let call = RuntimeCall::balances(balances::Call::transfer { to: Keyring::Bob.public(), amount: 1 });
+        assert_eq!(<Runtime as Dispatch>::weight(&call), 1_000);
+
+        // A block of 100 transfers uses exactly the maximum weight.
+        let extrinsics = (0..100)
+            .map(|nonce| transfer(&runtime, Keyring::Alice, nonce, Keyring::Bob, 1))
+            .collect();
+        let block = runtime.build_block(extrinsics);
+
+        // One more transfer is too much.
+        let mut overweight = block.clone();
+        overweight.extrinsics.push(transfer(&runtime, Keyring::Alice, 100, Keyring::Bob, 1));
+        overweight.header.extrinsics_root = support::extrinsics_root(&overweight.extrinsics);
+        assert_eq!(
+            runtime.execute_block(overweight),
+            Err(system::Error::BlockWeightExceeded.into())
+        );
+        let receipts = (0..100).map(|index| transfer_receipt(index, Keyring::Alice, Keyring::Bob, 1)).collect();
+        assert_eq!(runtime.execute_block(block), Ok(types::BlockReceipt { extrinsics: receipts }));
+    }
+
+    #[test]
+    fn privileged_calls_need_root() {
+        let mut runtime = new_runtime();
+        let alice = Keyring::Alice.public();
+        let force_set_balance =
+            RuntimeCall::balances(balances::Call::force_set_balance { who: alice, amount: 1_000 });
+
+        // Alice can sign the call, but it fails with a bad origin, and only the fee is paid.
+        let extrinsic = support::Extrinsic::new_signed(
+            &Keyring::Alice.pair(),
+            0,
+            force_set_balance.clone(),
+            runtime.system.genesis_hash(),
+        );
+        assert_eq!(
+            runtime.apply_extrinsic(0, extrinsic).map(|receipt| receipt.result),
+            Ok(Err(support::DispatchError::BadOrigin))
+        );
+        assert_eq!(runtime.balances.balance(&alice), 99);
+
+        assert_eq!(
+            runtime.dispatch(support::Origin::Signed(alice), force_set_balance.clone()),
+            Err(support::DispatchError::BadOrigin)
+        );
+        assert_eq!(runtime.dispatch(support::Origin::Root, force_set_balance), Ok(()));
+        assert_eq!(runtime.balances.balance(&alice), 1_000);
+    }
+
+    #[test]
+    fn sudo_key_dispatches_privileged_calls() {
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        let mut runtime = Runtime::from_genesis(GenesisConfig {
+            balances: balances::GenesisConfig { balances: vec![(bob, 100)] },
+            sudo: sudo::GenesisConfig { key: Some(bob) },
+            ..Default::default()
+        });
+
+        let signed_by_bob = |nonce, call| {
+            support::Extrinsic::new_signed(&Keyring::Bob.pair(), nonce, call, runtime.system.genesis_hash())
+        };
+        let force_set_balance = |who, amount| {
+            Box::new(RuntimeCall::balances(balances::Call::force_set_balance { who, amount }))
+        };
+        let extrinsics = vec![
+            signed_by_bob(0, RuntimeCall::sudo(sudo::Call::sudo { call: force_set_balance(alice, 1_000) })),
+            signed_by_bob(
+                1,
+                RuntimeCall::sudo(sudo::Call::sudo_as {
+                    who: alice,
+                    call: Box::new(RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 400 })),
+                }),
+            ),
+            // Alice does not have that much, but the sudo call itself succeeds.
+            signed_by_bob(
+                2,
+                RuntimeCall::sudo(sudo::Call::sudo_as {
+                    who: alice,
+                    call: Box::new(RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 10_000 })),
+                }),
+            ),
+            // A transfer needs a signed origin, so it fails without one.
+            signed_by_bob(
+                3,
+                RuntimeCall::sudo(sudo::Call::sudo_unsigned {
+                    call: Box::new(RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 1 })),
+                }),
+            ),
+            signed_by_bob(4, RuntimeCall::sudo(sudo::Call::set_key { new: alice })),
+            // Bob is not the key anymore.
+            signed_by_bob(5, RuntimeCall::sudo(sudo::Call::sudo { call: force_set_balance(bob, 1_000) })),
+        ];
+        // The weight of a sudo call includes the weight of the call it makes.
+        assert_eq!(<Runtime as Dispatch>::weight(&extrinsics[0].call), 600);
+
+        let block = runtime.build_block(extrinsics);
+        assert_eq!(block.extrinsics.len(), 6);
+        let receipt = runtime.execute_block(block).unwrap();
+        let results = receipt.extrinsics.iter().map(|extrinsic| extrinsic.result).collect::<Vec<_>>();
+        assert_eq!(results, vec![Ok(()), Ok(()), Ok(()), Ok(()), Ok(()), Err(sudo::Error::RequireSudo.into())]);
+        assert_eq!(runtime.balances.balance(&alice), 600);
+        // Bob paid the fees of the six extrinsics, even the last one which failed.
+        assert_eq!(runtime.balances.balance(&bob), 491);
+        assert_eq!(runtime.sudo.key(), Some(alice));
+
+        // The sudo pallet reports the result of each call it made.
+        let sudo_events = receipt
+            .extrinsics
+            .iter()
+            .flat_map(|extrinsic| extrinsic.events.iter())
+            .filter_map(|event| match event {
+                RuntimeEvent::sudo(event) => Some(event.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sudo_events,
+            vec![
+                sudo::Event::Sudid { result: Ok(()) },
+                sudo::Event::SudoAsDone { result: Ok(()) },
+                sudo::Event::SudoAsDone { result: Err(balances::Error::InsufficientBalance.into()) },
+                sudo::Event::SudoUnsignedDone { result: Err(support::DispatchError::BadOrigin) },
+                sudo::Event::KeyChanged { old: bob, new: alice },
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_deeply_nested_calls() {
+        let runtime = new_runtime();
+        // A transfer inside `depth - 1` sudo calls.
+        let nested = |depth: u32| {
+            let mut call = RuntimeCall::balances(balances::Call::transfer { to: Keyring::Bob.public(), amount: 1 });
+            for _ in 1..depth {
+                call = RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(call) });
+            }
+            call
+        };
+        let max_depth = <Runtime as system::Config>::MAX_CALL_DEPTH;
+        let call = nested(max_depth);
+        assert_eq!(codec::decode_all::<RuntimeCall>(&call.encode()), Ok(call));
+        let too_deep = nested(max_depth + 1);
+        assert_eq!(codec::decode_all::<RuntimeCall>(&too_deep.encode()), Err(codec::Error::TooDeep));
+
+        // Decoding stops at the limit, so a block worth of nested calls cannot overflow the stack.
+        let mut encoded = [3u8, 0].repeat(30_000);
+        encoded.extend(nested(1).encode());
+        assert_eq!(codec::decode_all::<RuntimeCall>(&encoded), Err(codec::Error::TooDeep));
+
+        // Calls built in memory are checked too.
+        let extrinsic =
+            support::Extrinsic::new_signed(&Keyring::Alice.pair(), 0, too_deep, runtime.system.genesis_hash());
+        assert_eq!(runtime.validate_transaction(&extrinsic), Err(system::Error::CallTooDeep.into()));
+    }
+
+    #[test]
+    fn encode_and_decode_blocks() {
+        let mut runtime = new_runtime();
+        runtime.balances.set_balance(&Keyring::Bob.public(), 10);
+        let claim = support::Extrinsic::new_signed(
+            &Keyring::Bob.pair(),
+            0,
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+                claim: "my_document".to_string(),
+            }),
+            runtime.system.genesis_hash(),
+        );
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30), claim]);
+
+        let encoded = block.encode();
+        assert_eq!(codec::decode_all::<types::Block>(&encoded), Ok(block.clone()));
+        // The header is encoded first, followed by the number of extrinsics.
+        assert_eq!(encoded[..4], 1u32.to_le_bytes());
+        assert_eq!(encoded[100], 2);
+
+        // Calls are identified by the index of the pallet, then the index of the call.
+        assert_eq!(block.extrinsics[0].call.encode()[..2], [1, 0]);
+        assert_eq!(block.extrinsics[1].call.encode()[..2], [2, 1]);
+        assert_eq!(
+            codec::decode_all::<RuntimeCall>(&[5, 0]),
+            Err(codec::Error::InvalidVariant(5))
+        );
+        assert_eq!(
+            codec::decode_all::<RuntimeCall>(&[1, 7]),
+            Err(codec::Error::InvalidVariant(7))
+        );
+    }
+
+    #[test]
+    fn blocks_and_state_as_json() {
+        let mut runtime = new_runtime();
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30)]);
+
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["header"]["block_number"], 1);
+        assert_eq!(json["header"]["parent_hash"], support::hex::encode(&runtime.system.genesis_hash()));
+        assert_eq!(
+            json["extrinsics"][0]["call"]["balances"]["transfer"]["to"],
+            support::hex::encode(&Keyring::Bob.public().0)
+        );
+        assert_eq!(json["extrinsics"][0]["call"]["balances"]["transfer"]["amount"], 30);
+        assert_eq!(serde_json::from_value::<types::Block>(json).unwrap(), block);
+
+        runtime.execute_block(block).unwrap();
+        let state = serde_json::to_value(&runtime).unwrap();
+        let bob = support::hex::encode(&Keyring::Bob.public().0);
+        let alice = support::hex::encode(&Keyring::Alice.public().0);
+        assert_eq!(state["system"]["block_number"], 1);
+        assert_eq!(state["system"]["nonce"][&alice], 1);
+        // Alice also paid a fee of 2 for the transfer.
+        assert_eq!(state["balances"]["balances"][&alice], 68);
+        assert_eq!(state["balances"]["balances"][&bob], 30);
+        assert_eq!(state["proof_of_existence"]["claims"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn restart_from_disk() {
+        let dir = super::store::test_dir("restart_from_disk");
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+
+        let store = super::store::Store::open(&dir).unwrap();
+        let mut runtime = super::open_chain(super::chain_spec::ChainSpec::dev(), &store).unwrap();
+        let claim = support::Extrinsic::new_signed(
+            &Keyring::Alice.pair(),
+            1,
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                claim: "my_document".to_string(),
+            }),
+            runtime.system.genesis_hash(),
+        );
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30), claim]);
+        super::import_block(&mut runtime, &store, block.clone()).unwrap();
+        drop(store);
+
+        // The node starts again after the last imported block, with the same state.
+        let store = super::store::Store::open(&dir).unwrap();
+        let mut restarted = super::open_chain(super::chain_spec::ChainSpec::dev(), &store).unwrap();
+        assert_eq!(restarted.system.block_number(), 1);
+        assert_eq!(restarted.storage(), runtime.storage());
+        assert_eq!(restarted.balances.balance(&bob), 30);
+        assert_eq!(restarted.proof_of_existence.get_claim(&"my_document".to_string()), Some(alice));
+        assert_eq!(store.block::<types::Block>(1).unwrap(), Some(block));
+
+        let next = restarted.build_block(vec![transfer(&restarted, Keyring::Alice, 2, Keyring::Bob, 10)]);
+        assert_eq!(next.extrinsics.len(), 1);
+        assert_eq!(
+            runtime.execute_block(next.clone()),
+            Ok(types::BlockReceipt { extrinsics: vec![transfer_receipt(0, Keyring::Alice, Keyring::Bob, 10)] })
+        );
+        super::import_block(&mut restarted, &store, next).unwrap();
+
+        // The data of a chain cannot be used to start another chain.
+        let other = super::chain_spec::ChainSpec { name: "Other".to_string(), genesis: GenesisConfig::default() };
+        assert!(matches!(super::open_chain(other, &store), Err(super::store::Error::GenesisMismatch)));
+
+        // A state with an entry which does not decode as the type of its item is rejected.
+        let mut state = restarted.storage();
+        let bob_balance = restarted.balances.balance(&bob).encode();
+        let balance = state.iter_mut().find(|(_, value)| *value == bob_balance).unwrap();
+        balance.1 = vec![1, 2, 3];
+        store.save_state(&state).unwrap();
+        assert!(matches!(
+            super::open_chain(super::chain_spec::ChainSpec::dev(), &store),
+            Err(super::store::Error::Codec(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prove_balances_and_claims() {
+        let mut runtime = new_runtime();
+        let alice = Keyring::Alice.public();
+        let bob = Keyring::Bob.public();
+        let claim = support::Extrinsic::new_signed(
+            &Keyring::Alice.pair(),
+            1,
+            RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+                claim: "my_document".to_string(),
+            }),
+            runtime.system.genesis_hash(),
+        );
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 30), claim]);
+        let header = block.header.clone();
+        assert!(runtime.execute_block(block).is_ok());
+
+        // The proofs only need the header of the block to be checked.
+        let balance_proof = runtime.prove(&runtime.balances.balance_key(&bob)).unwrap();
+        let claim_proof = runtime.prove(&runtime.proof_of_existence.claim_key(&"my_document".to_string())).unwrap();
+        assert_eq!(balances::Pallet::<Runtime>::verify_balance(&header, &bob, &balance_proof), Some(30));
+        assert_eq!(
+            proof_of_existence::Pallet::<Runtime>::verify_claim(&header, &"my_document".to_string(), &claim_proof),
+            Some(alice)
+        );
+
+        // A proof is only valid for its own entry, with its own value, in its own state.
+        assert_eq!(balances::Pallet::<Runtime>::verify_balance(&header, &alice, &balance_proof), None);
+        let mut wrong_value = balance_proof.clone();
+        wrong_value.value = 1_000u128.encode();
+        assert_eq!(balances::Pallet::<Runtime>::verify_balance(&header, &bob, &wrong_value), None);
+        let mut wrong_header = header.clone();
+        wrong_header.state_root = new_runtime().state_root();
+        assert_eq!(balances::Pallet::<Runtime>::verify_balance(&wrong_header, &bob, &balance_proof), None);
+        assert_eq!(runtime.prove(&runtime.balances.balance_key(&Keyring::Charlie.public())), None);
+    }
+
+    #[test]
+    fn trace_block_execution() {
+        use super::trace::{Span, Subscriber};
+
+        #[derive(Default)]
+        struct Recorder(std::cell::RefCell<Vec<(Span, Option<support::DispatchResult>)>>);
+
+        impl Subscriber for Recorder {
+            fn enter(&self, span: &Span) {
+                self.0.borrow_mut().push((span.clone(), None));
+            }
+
+            fn exit(&self, span: &Span, outcome: &support::DispatchResult) {
+                self.0.borrow_mut().push((span.clone(), Some(*outcome)));
+            }
+        }
+
+        let mut runtime = new_runtime();
+        let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0, Keyring::Bob, 500)]);
+        let recorder = std::rc::Rc::new(Recorder::default());
+        super::trace::set_subscriber(Some(recorder.clone()));
+        let receipt = runtime.execute_block(block).unwrap();
+        super::trace::set_subscriber(None);
+        assert_eq!(receipt.extrinsics[0].result, Err(balances::Error::InsufficientBalance.into()));
+
+        let alice = format!("{:?}", Keyring::Alice.public());
+        let block_span = Span::Block { number: "1".to_string() };
+        let extrinsic_span = Span::Extrinsic { index: 0, caller: alice };
+        let call_span = Span::Call {
+            pallet: "balances",
+            call: "transfer",
+            origin: format!("{:?}", support::Origin::Signed(Keyring::Alice.public())),
+        };
+        assert_eq!(
+            recorder.0.take(),
+            vec![
+                (block_span.clone(), None),
+                (extrinsic_span.clone(), None),
+                (call_span.clone(), None),
+                (call_span, Some(Err(balances::Error::InsufficientBalance.into()))),
+                (extrinsic_span, Some(Ok(()))),
+                (block_span, Some(Ok(()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn runtime_metadata() {
+        let metadata = Runtime::metadata();
+        let pallets = metadata.pallets.iter().map(|p| (p.name, p.index)).collect::<Vec<_>>();
+        assert_eq!(pallets, vec![("system", 0), ("balances", 1), ("proof_of_existence", 2), ("sudo", 3)]);
+
+        let balances = &metadata.pallets[1];
+        let transfer = &balances.calls[0];
+        assert_eq!((transfer.name, transfer.index), ("transfer", 0));
+        assert_eq!(transfer.docs[0], "Transfer `amount` from one account to another.");
+        assert_eq!(
+            balances.errors.iter().map(|e| (e.name, e.index)).collect::<Vec<_>>(),
+            vec![("InsufficientBalance", 0), ("BalanceOverflow", 1), ("CannotPayFee", 2)]
+        );
+        assert_eq!(balances.errors[0].docs, vec!["The sender does not have enough balance for the transfer."]);
+        assert_eq!(balances.events.iter().map(|e| e.name).collect::<Vec<_>>(), vec!["Transfer", "FeePaid", "BalanceSet"]);
+        assert!(metadata.pallets[0].calls.is_empty());
+
+        // The types are the ones of the runtime, not the ones written in the pallets.
+        let account_id = "PublicKey";
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(
+            json["pallets"][1]["calls"][0]["args"],
+            serde_json::json!([
+                { "name": "to", "type": account_id },
+                { "name": "amount", "type": "u128" },
+            ])
+        );
+        assert_eq!(
+            json["pallets"][1]["events"][1]["fields"],
+            serde_json::json!([
+                { "name": "who", "type": account_id },
+                { "name": "amount", "type": "u128" },
+            ])
+        );
+        assert_eq!(
+            json["pallets"][3]["calls"][2]["args"],
+            serde_json::json!([
+                { "name": "who", "type": account_id },
+                { "name": "call", "type": "Box<RuntimeCall>" },
+            ])
+        );
+        assert_eq!(
+            json["pallets"][3]["events"][1]["fields"],
+            serde_json::json!([{ "name": "result", "type": "Result<(), DispatchError>" }])
+        );
+    }
+}
+
+// A runtime with two pallets whose hooks do some work, to check when and in which order the
+// runtime runs the hooks. The runtime macro generates functions which these tests do not use.
+#[cfg(test)]
+#[allow(dead_code)]
+mod hooks_tests {
+    use std::cell::RefCell;
+
+    use crate::keyring::Keyring;
+    use crate::support::{self, Dispatch};
+    use crate::system::{EventRecord, Phase};
+    use crate::{storage, system};
+
+    thread_local! {
+        // The hooks and calls of the pallets, in the order they ran.
+        static CALLS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(call: &'static str) {
+        CALLS.with(|calls| calls.borrow_mut().push(call));
+    }
+
+    fn take_calls() -> Vec<&'static str> {
+        CALLS.with(|calls| calls.take())
+    }
+
+    // A pallet whose hooks record that they ran, deposit an event and return `$weight` and
+    // `$weight + 1`, with a call which records that it ran.
+    macro_rules! hooks_pallet {
+        ($name:ident, $weight:expr) => {
+            mod $name {
+                use crate::storage::{Entries, PalletStorage, Storage};
+                use crate::support::{
+                    ensure_signed, DispatchQueue, DispatchResult, Hooks, PalletError, Weight,
+                };
+                use crate::system::OriginFor;
+
+                pub trait Config: crate::system::Config {}
+
+                pub struct Pallet<T: Config> {
+                    events: Vec<Event<T>>,
+                }
+
+                #[macros::event]
+                pub enum Event<T: Config> {
+                    Initialized { block_number: T::BlockNumber },
+                    Pinged { number: u32 },
+                    Finalized { block_number: T::BlockNumber },
+                }
+
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub enum Error {}
+
+                impl PalletError for Error {
+                    const VARIANTS: &'static [Self] = &[];
+
+                    fn index(&self) -> u8 {
+                        match *self {}
+                    }
+
+                    fn name(&self) -> &'static str {
+                        match *self {}
+                    }
+
+                    fn docs(&self) -> &'static str {
+                        match *self {}
+                    }
+                }
+
+                #[derive(serde::Serialize, serde::Deserialize)]
+                #[serde(bound = "")]
+                pub struct GenesisConfig<T: Config> {
+                    #[serde(skip)]
+                    _config: core::marker::PhantomData<T>,
+                }
+
+                impl<T: Config> Default for GenesisConfig<T> {
+                    fn default() -> Self {
+                        Self { _config: core::marker::PhantomData }
+                    }
+                }
+
+                impl<T: Config> GenesisConfig<T> {
+                    pub fn build(self, _storage: &Storage<T::Backend>) -> Pallet<T> {
+                        Pallet { events: Vec::new() }
+                    }
+                }
+
+                #[macros::call]
+                impl<T: Config> Pallet<T> {
+                    #[weight(100)]
+                    pub fn ping(
+                        &mut self,
+                        origin: OriginFor<T>,
+                        from: T::AccountId,
+                    ) -> DispatchResult {
+                        if ensure_signed(origin)? != from {
+                            return Err(crate::support::DispatchError::BadOrigin);
+                        }
+                        super::record(concat!(stringify!($name), "::ping"));
+                        self.events.push(Event::Pinged { number: $weight });
+                        Ok(())
+                    }
+                }
+
+                impl<T: Config> Pallet<T> {
+                    pub fn take_events(&mut self) -> Vec<Event<T>> {
+                        core::mem::take(&mut self.events)
+                    }
+                }
+
+                impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {
+                    fn on_initialize(&mut self, block_number: T::BlockNumber) -> Weight {
+                        super::record(concat!(stringify!($name), "::on_initialize"));
+                        self.events.push(Event::Initialized { block_number });
+                        $weight
+                    }
+
+                    fn on_finalize(&mut self, block_number: T::BlockNumber) -> Weight {
+                        super::record(concat!(stringify!($name), "::on_finalize"));
+                        self.events.push(Event::Finalized { block_number });
+                        $weight + 1
+                    }
+                }
+
+                impl<T: Config, Call> DispatchQueue<OriginFor<T>, Call> for Pallet<T> {}
+
+                impl<T: Config> PalletStorage for Pallet<T> {
+                    fn storage(&self) -> Entries {
+                        Vec::new()
+                    }
+
+                    fn restore(
+                        &mut self,
+                        _storage: &[(Vec<u8>, Vec<u8>)],
+                    ) -> Result<(), crate::codec::Error> {
+                        Ok(())
+                    }
+                }
+            }
+        };
+    }
+
+    hooks_pallet!(first, 10);
+    hooks_pallet!(second, 20);
+
+    mod types {
+        use crate::support;
+
+        pub type AccountId = support::PublicKey;
+        pub type Extrinsic = support::Extrinsic<AccountId, u32, super::RuntimeCall>;
+        pub type Header = support::Header<u32>;
+        pub type Block = support::Block<Header, Extrinsic>;
+        pub type BlockReceipt = support::BlockReceipt<AccountId, super::RuntimeEvent>;
+        pub type ExtrinsicReceipt = support::ExtrinsicReceipt<AccountId, super::RuntimeEvent>;
+    }
+
+    impl system::Config for Runtime {
+        type AccountId = types::AccountId;
+        type BlockNumber = u32;
+        type Nonce = u32;
+        type Backend = storage::MemoryBackend;
+        type RuntimeEvent = RuntimeEvent;
+        const MAX_BLOCK_WEIGHT: support::Weight = 150;
+        const MAX_BLOCK_LENGTH: usize = 1024;
+        const MAX_CALL_DEPTH: u32 = 4;
+    }
+
+    impl first::Config for Runtime {}
+
+    impl second::Config for Runtime {}
+
+    // Extrinsics of this runtime are free.
+    impl support::TransactionPayment<types::AccountId> for Runtime {
+        type Balance = u128;
+
+        fn fee(_weight: support::Weight, _length: usize) -> u128 {
+            0
+        }
+
+        fn can_pay_fee(&self, _who: &types::AccountId, _fee: u128) -> support::DispatchResult {
+            Ok(())
+        }
+
+        fn withdraw_fee(&mut self, _who: &types::AccountId, _fee: u128) -> support::DispatchResult {
+            Ok(())
+        }
+    }
+
+    #[macros::runtime]
+    pub struct Runtime {
+        system: system::Pallet<Runtime>,
+        first: first::Pallet<Runtime>,
+        second: second::Pallet<Runtime>,
+    }
+
+    #[test]
+    fn run_hooks_around_extrinsics() {
+        let mut runtime = Runtime::from_genesis(GenesisConfig::default());
+        let alice = Keyring::Alice.public();
+        let genesis_hash = runtime.system.genesis_hash();
+        let ping = |nonce| {
+            support::Extrinsic::new_signed(
+                &Keyring::Alice.pair(),
+                nonce,
+                RuntimeCall::second(second::Call::ping { from: alice }),
+                genesis_hash,
+            )
+        };
+
+        // Building a block runs the hooks too, but changes nothing.
+        let block = runtime.build_block(vec![ping(0)]);
+        assert_eq!(block.extrinsics.len(), 1);
+        assert_eq!(runtime.system.block_weight(), 0);
+        take_calls();
+
+        // The hooks of every pallet run in the order the pallets are declared, before and after
+        // the extrinsics.
+        assert!(runtime.execute_block(block).is_ok());
+        assert_eq!(
+            take_calls(),
+            vec![
+                "first::on_initialize",
+                "second::on_initialize",
+                "second::ping",
+                "first::on_finalize",
+                "second::on_finalize",
+            ]
+        );
+        assert_eq!(runtime.system.block_weight(), 10 + 20 + 100 + 11 + 21);
+        assert_eq!(
+            runtime.system.events(),
+            &[
+                EventRecord {
+                    phase: Phase::Initialization,
+                    event: RuntimeEvent::first(first::Event::Initialized { block_number: 1 }),
+                },
+                EventRecord {
+                    phase: Phase::Initialization,
+                    event: RuntimeEvent::second(second::Event::Initialized { block_number: 1 }),
+                },
+                EventRecord {
+                    phase: Phase::ApplyExtrinsic(0),
+                    event: RuntimeEvent::second(second::Event::Pinged { number: 20 }),
+                },
+                EventRecord {
+                    phase: Phase::Finalization,
+                    event: RuntimeEvent::first(first::Event::Finalized { block_number: 1 }),
+                },
+                EventRecord {
+                    phase: Phase::Finalization,
+                    event: RuntimeEvent::second(second::Event::Finalized { block_number: 1 }),
+                },
+            ]
+        );
+
+        // The weight of the hooks counts toward the maximum block weight, so only one more ping
+        // fits, and the weight of the finalization counts even beyond the maximum. The events of
+        // the previous block are cleared.
+        let block = runtime.build_block(vec![ping(1), ping(2)]);
+        assert_eq!(block.extrinsics.len(), 1);
+        assert!(runtime.execute_block(block).is_ok());
+        assert_eq!(runtime.system.block_weight(), 162);
+        let phases = runtime.system.events().iter().map(|record| record.phase).collect::<Vec<_>>();
+        assert_eq!(
+            phases,
+            vec![
+                Phase::Initialization,
+                Phase::Initialization,
+                Phase::ApplyExtrinsic(0),
+                Phase::Finalization,
+                Phase::Finalization,
+            ]
+        );
+    }
 }
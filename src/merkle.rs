@@ -0,0 +1,177 @@
+use crate::codec::{self, Decode, Encode};
+use crate::support::{hash, Hash};
+
+/// A binary Merkle tree over storage entries, whose root commits to the whole state of the runtime.
+///
+/// The leaves are the entries in key order. Each level hashes the nodes of the level below in
+/// pairs, and a node left without a pair moves up to the next level as it is. Leaves and inner
+/// nodes are hashed with a different first byte, so a leaf can never pass for an inner node.
+///
+/// The root of an empty state is the zero hash.
+pub fn root(entries: &[(Vec<u8>, Vec<u8>)]) -> Hash {
+	let mut level = entries.iter().map(|(key, value)| leaf_hash(key, value)).collect::<Vec<_>>();
+	if level.is_empty() {
+		return Hash::default();
+	}
+	while level.len() > 1 {
+		level = parent_level(&level);
+	}
+	level[0]
+}
+
+/// A proof that an entry is in a state with a given root, for clients which only know the state
+/// root of a header. It holds the entry, and the hashes needed to rebuild the path from its leaf
+/// to the root: the proof of one entry among `n` has about `log2(n)` hashes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proof {
+	/// The key of the entry in storage.
+	pub key: Vec<u8>,
+	/// The encoded value of the entry.
+	pub value: Vec<u8>,
+	/// The position of the leaf of the entry.
+	pub index: u64,
+	/// The number of leaves of the tree.
+	pub leaves: u64,
+	/// The hashes of the other child of each node on the path from the leaf to the root, from the
+	/// bottom up. Nodes without a pair have none.
+	pub siblings: Vec<Hash>,
+}
+
+impl Encode for Proof {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(&self.key, &self.value, self.index).encode_to(dest);
+		(self.leaves, &self.siblings).encode_to(dest);
+	}
+}
+
+impl Decode for Proof {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		let (key, value, index) = Decode::decode(input)?;
+		let (leaves, siblings) = Decode::decode(input)?;
+		Ok(Self { key, value, index, leaves, siblings })
+	}
+}
+
+/// Prove that the entry under `key` is in `entries`, which must be in key order. Returns `None`
+/// if there is no such entry.
+pub fn prove(entries: &[(Vec<u8>, Vec<u8>)], key: &[u8]) -> Option<Proof> {
+	let index = entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)).ok()?;
+	let mut level = entries.iter().map(|(key, value)| leaf_hash(key, value)).collect::<Vec<_>>();
+	let mut siblings = Vec::new();
+	let mut position = index;
+	while level.len() > 1 {
+		if let Some(sibling) = level.get(position ^ 1) {
+			siblings.push(*sibling);
+		}
+		level = parent_level(&level);
+		position /= 2;
+	}
+	Some(Proof {
+		key: key.to_vec(),
+		value: entries[index].1.clone(),
+		index: index as u64,
+		leaves: entries.len() as u64,
+		siblings,
+	})
+}
+
+/// Check that `proof` proves its entry is in a state whose root is `root`.
+pub fn verify(root: &Hash, proof: &Proof) -> bool {
+	if proof.index >= proof.leaves {
+		return false;
+	}
+	let mut node = leaf_hash(&proof.key, &proof.value);
+	let mut siblings = proof.siblings.iter();
+	let (mut position, mut len) = (proof.index, proof.leaves);
+	while len > 1 {
+		// The last node of a level with an odd length has no pair, and moves up as it is.
+		if position % 2 == 1 || position + 1 < len {
+			let Some(sibling) = siblings.next() else { return false };
+			node = if position % 2 == 1 { node_hash(sibling, &node) } else { node_hash(&node, sibling) };
+		}
+		position /= 2;
+		len = len.div_ceil(2);
+	}
+	siblings.next().is_none() && node == *root
+}
+
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+
+// The hash of the leaf for the entry `key`, `value`.
+fn leaf_hash(key: &[u8], value: &[u8]) -> Hash {
+	let mut data = vec![LEAF];
+	(key, value).encode_to(&mut data);
+	hash(&data)
+}
+
+// The hash of an inner node, from the hashes of its two children.
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+	let mut data = vec![NODE];
+	data.extend_from_slice(left);
+	data.extend_from_slice(right);
+	hash(&data)
+}
+
+// The level of the tree above `level`.
+fn parent_level(level: &[Hash]) -> Vec<Hash> {
+	level
+		.chunks(2)
+		.map(|pair| match pair {
+			[left, right] => node_hash(left, right),
+			[single] => *single,
+			_ => unreachable!("chunks have one or two nodes"),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{leaf_hash, node_hash, prove, root, verify};
+	use crate::support::Hash;
+
+	#[test]
+	fn root_commits_to_every_entry() {
+		assert_eq!(root(&[]), Hash::default());
+
+		let entries = vec![(vec![1], vec![10]), (vec![2], vec![20]), (vec![3], vec![30])];
+		let a = leaf_hash(&[1], &[10]);
+		let b = leaf_hash(&[2], &[20]);
+		let c = leaf_hash(&[3], &[30]);
+		assert_eq!(root(&entries[..1]), a);
+		assert_eq!(root(&entries), node_hash(&node_hash(&a, &b), &c));
+
+		// Changing, adding or removing any entry changes the root.
+		let mut changed = entries.clone();
+		changed[2].1 = vec![31];
+		assert_ne!(root(&changed), root(&entries));
+		changed.push((vec![4], vec![40]));
+		assert_ne!(root(&changed), root(&entries));
+		assert_ne!(root(&entries[..2]), root(&entries));
+	}
+
+	#[test]
+	fn prove_and_verify_entries() {
+		for len in 1..=9u8 {
+			let entries = (0..len).map(|i| (vec![i], vec![i * 10])).collect::<Vec<_>>();
+			let root = root(&entries);
+			for i in 0..len {
+				let proof = prove(&entries, &[i]).unwrap();
+				assert_eq!(proof.value, vec![i * 10]);
+				assert!(verify(&root, &proof), "entry {} of {}", i, len);
+
+				let mut wrong_value = proof.clone();
+				wrong_value.value = vec![1];
+				assert!(!verify(&root, &wrong_value));
+				let mut wrong_index = proof.clone();
+				wrong_index.index = (proof.index + 1) % proof.leaves;
+				assert!(len == 1 || !verify(&root, &wrong_index));
+				let mut missing_sibling = proof;
+				if missing_sibling.siblings.pop().is_some() {
+					assert!(!verify(&root, &missing_sibling));
+				}
+			}
+		}
+		assert_eq!(prove(&[(vec![1], vec![10])], &[2]), None);
+	}
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+use crate::support::PalletError;
+
+/// A description of a runtime: what clients can call, and what they can get back. It is generated
+/// by the macros, with `Runtime::metadata()`, and can be written as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeMetadata {
+	/// The pallets of the runtime, in the order of their indices. System is always first.
+	pub pallets: Vec<PalletMetadata>,
+}
+
+/// A description of a pallet of a runtime.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PalletMetadata {
+	/// The name of the pallet in the runtime.
+	pub name: &'static str,
+	/// The index of the pallet, which identifies it in encoded calls and in errors.
+	pub index: u8,
+	/// The calls of the pallet, in the order of their indices.
+	pub calls: Vec<CallMetadata>,
+	/// The events the pallet can deposit.
+	pub events: Vec<EventMetadata>,
+	/// The errors the pallet can return, in the order of their indices.
+	pub errors: Vec<ErrorMetadata>,
+}
+
+/// A description of a call of a pallet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallMetadata {
+	/// The name of the call.
+	pub name: &'static str,
+	/// The index of the call, which identifies it in encoded calls.
+	pub index: u8,
+	/// The arguments of the call, after its origin.
+	pub args: Vec<FieldMetadata>,
+	/// The documentation of the call.
+	pub docs: Vec<&'static str>,
+}
+
+/// A description of an event of a pallet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventMetadata {
+	/// The name of the event.
+	pub name: &'static str,
+	/// The fields of the event.
+	pub fields: Vec<FieldMetadata>,
+	/// The documentation of the event.
+	pub docs: Vec<&'static str>,
+}
+
+/// A description of an argument of a call, or a field of an event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldMetadata {
+	/// The name of the argument or field.
+	pub name: &'static str,
+	/// The type of the argument or field in the runtime, as described by `TypeInfo`, like `u128`
+	/// for `T::Balance`.
+	#[serde(rename = "type")]
+	pub type_name: String,
+}
+
+/// A description of an error of a pallet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorMetadata {
+	/// The name of the error.
+	pub name: &'static str,
+	/// The index of the error in the errors of its pallet.
+	pub index: u8,
+	/// The documentation of the error.
+	pub docs: Vec<&'static str>,
+}
+
+/// Describe every variant of the error type `E` of a pallet.
+pub fn errors<E: PalletError>() -> Vec<ErrorMetadata> {
+	E::VARIANTS
+		.iter()
+		.map(|error| ErrorMetadata { name: error.name(), index: error.index(), docs: vec![error.docs()] })
+		.collect()
+}
+
+/// A type which can appear in the arguments of a call or the fields of an event.
+///
+/// The description of each type is written by hand, so the metadata is the same whichever compiler
+/// built the runtime. Types which are not described here can not be used in calls or events.
+pub trait TypeInfo {
+	/// The description of the type, like `u128`, `Vec<u8>` or `Option<PublicKey>`.
+	fn type_name() -> String;
+}
+
+macro_rules! impl_type_info {
+	($($type_:ty),*) => {
+		$(
+			impl TypeInfo for $type_ {
+				fn type_name() -> String {
+					stringify!($type_).to_string()
+				}
+			}
+		)*
+	};
+}
+
+impl_type_info!(u8, u16, u32, u64, u128, bool, String, ());
+
+impl<const N: usize> TypeInfo for [u8; N] {
+	fn type_name() -> String {
+		format!("[u8; {}]", N)
+	}
+}
+
+impl<T: TypeInfo> TypeInfo for Vec<T> {
+	fn type_name() -> String {
+		format!("Vec<{}>", T::type_name())
+	}
+}
+
+impl<T: TypeInfo> TypeInfo for Option<T> {
+	fn type_name() -> String {
+		format!("Option<{}>", T::type_name())
+	}
+}
+
+impl<T: TypeInfo> TypeInfo for Box<T> {
+	fn type_name() -> String {
+		format!("Box<{}>", T::type_name())
+	}
+}
+
+impl<T: TypeInfo, E: TypeInfo> TypeInfo for Result<T, E> {
+	fn type_name() -> String {
+		format!("Result<{}, {}>", T::type_name(), E::type_name())
+	}
+}
@@ -1,33 +1,125 @@
 use core::fmt::Debug;
-use std::collections::BTreeMap;
 
-use crate::{support::DispatchResult, types::AccountId};
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{self, Decode, Encode};
+use crate::merkle::Proof;
+use crate::storage::{Entries, PalletStorage, Storage, StorageMap};
+use crate::support::{ensure_signed, DispatchQueue, DispatchResult, Header, Hooks, PalletError};
+use crate::system::OriginFor;
 
 pub trait Config: crate::system::Config {
 	/// The type which represents the content that can be claimed using this pallet.
 	/// Could be the content directly as bytes, or better yet the hash of that content.
 	/// We leave that decision to the runtime developer.
-	type Content: Debug + Ord;
+	type Content: Debug + Ord + Clone + Encode + Decode;
 }
 
 /// This is the Proof of Existence Module.
 /// It is a simple module that allows accounts to claim existence of some data.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(bound(serialize = "T::AccountId: Serialize, T::Content: Serialize"))]
 pub struct Pallet<T: Config> {
 	/// A simple storage map from content to the owner of that content.
 	/// Accounts can make multiple different claims, but each claim can only have one owner.
-    claims: BTreeMap<T::Content, T::AccountId>,
+    claims: StorageMap<T::Content, T::AccountId, T::Backend>,
+	/// Events deposited by this pallet which have not been collected by the runtime yet.
+	#[serde(skip)]
+	events: Vec<Event<T>>,
+}
+
+/// The events which can be deposited by the Proof of Existence Module.
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `owner` claimed `claim`.
+	ClaimCreated { owner: T::AccountId, claim: T::Content },
+	/// `owner` revoked their claim on `claim`.
+	ClaimRevoked { owner: T::AccountId, claim: T::Content },
+}
+
+/// The errors which can be returned by the Proof of Existence Module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The content has already been claimed by someone.
+	AlreadyClaimed,
+	/// There is no claim on the content.
+	ClaimNotFound,
+	/// The caller is not the owner of the claim.
+	NotClaimOwner,
+}
+
+impl PalletError for Error {
+	const VARIANTS: &'static [Self] = &[
+		Error::AlreadyClaimed,
+		Error::ClaimNotFound,
+		Error::NotClaimOwner,
+	];
+
+	fn index(&self) -> u8 {
+		*self as u8
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Error::AlreadyClaimed => "AlreadyClaimed",
+			Error::ClaimNotFound => "ClaimNotFound",
+			Error::NotClaimOwner => "NotClaimOwner",
+		}
+	}
+
+	fn docs(&self) -> &'static str {
+		match self {
+			Error::AlreadyClaimed => "The content has already been claimed by someone.",
+			Error::ClaimNotFound => "There is no claim on the content.",
+			Error::NotClaimOwner => "The caller is not the owner of the claim.",
+		}
+	}
+}
+
+/// The initial state of the Proof of Existence Module.
+#[derive(Serialize, Deserialize)]
+#[serde(
+	bound(
+		serialize = "T::AccountId: Serialize, T::Content: Serialize",
+		deserialize = "T::AccountId: Deserialize<'de>, T::Content: Deserialize<'de>"
+	),
+	default,
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The claims which already exist, with their owner.
+	pub claims: Vec<(T::Content, T::AccountId)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { claims: Vec::new() }
+	}
+}
+
+impl<T: Config> GenesisConfig<T> {
+	/// Build the Proof of Existence Module with this initial state, in `storage`.
+	pub fn build(self, storage: &Storage<T::Backend>) -> Pallet<T> {
+		let mut pallet = Pallet::new(storage);
+		for (claim, owner) in self.claims {
+			pallet.claims.insert(&claim, &owner);
+		}
+		pallet
+	}
 }
 
 #[macros::call]
 impl<T: Config> Pallet<T> {
-    /// Create a new claim on behalf of the `caller`.
+    /// Create a new claim on behalf of the signer of the call.
 	/// This function will return an error if someone already has claimed that content.
-	pub fn create_claim(&mut self, caller: T::AccountId, claim: T::Content) -> DispatchResult {
+	#[weight(1_500)]
+	pub fn create_claim(&mut self, origin: OriginFor<T>, claim: T::Content) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
         match self.get_claim(&claim) {
-            Some(_) => Err (&"this content is already claimed"),
+            Some(_) => Err(Error::AlreadyClaimed.into()),
             None => {
-                self.claims.insert(claim, caller);
+                self.claims.insert(&claim, &caller);
+                self.deposit_event(Event::ClaimCreated { owner: caller, claim });
                 Ok(())
             }
         }
@@ -36,14 +128,17 @@ impl<T: Config> Pallet<T> {
 	/// Revoke an existing claim on some content.
 	/// This function should only succeed if the caller is the owner of an existing claim.
 	/// It will return an error if the claim does not exist, or if the caller is not the owner.
-	pub fn revoke_claim(&mut self, caller: T::AccountId, claim: T::Content) -> DispatchResult {
-        let claim_owner = self.get_claim(&claim).ok_or("claim does not exist")?;
+	#[weight(1_000)]
+	pub fn revoke_claim(&mut self, origin: OriginFor<T>, claim: T::Content) -> DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let claim_owner = self.get_claim(&claim).ok_or(Error::ClaimNotFound)?;
 
-        if claim_owner != &caller {
-            return Err("Caller is not the owner of the claim");
+        if claim_owner != caller {
+            return Err(Error::NotClaimOwner.into());
         } 
 
         self.claims.remove(&claim);
+        self.deposit_event(Event::ClaimRevoked { owner: caller, claim });
 
         Ok(())
 	}
@@ -51,56 +146,115 @@ impl<T: Config> Pallet<T> {
 
 impl<T: Config> Pallet<T> {
 	/// Create a new instance of the Proof of Existence Module.
-	pub fn new() -> Self {
+	pub fn new(storage: &Storage<T::Backend>) -> Self {
         Self {
-            claims: BTreeMap::new()
+            claims: StorageMap::new(storage, "ProofOfExistence", "Claims"),
+            events: Vec::new(),
         }
 	}
 
+	/// Deposit an event, to be collected by the runtime at the end of the current extrinsic.
+	fn deposit_event(&mut self, event: Event<T>) {
+		self.events.push(event);
+	}
+
+	/// Take all the events deposited since the last time this was called.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		core::mem::take(&mut self.events)
+	}
+
     /// Get the owner (if any) of a claim.
-	pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
+	pub fn get_claim(&self, claim: &T::Content) -> Option<T::AccountId> {
         self.claims.get(claim)
 	}
+
+	/// The storage key of the claim on `claim`, to prove it with the runtime.
+	pub fn claim_key(&self, claim: &T::Content) -> Vec<u8> {
+		self.claims.storage_key(claim)
+	}
+
+	/// Check a proof of the claim on `claim` against the state root of `header`, without any
+	/// state. Returns the owner of the claim it proves, or `None` if the proof is not valid.
+	pub fn verify_claim(
+		header: &Header<T::BlockNumber>,
+		claim: &T::Content,
+		proof: &Proof,
+	) -> Option<T::AccountId> {
+		Self::new(&Storage::default()).claims.verify(&header.state_root, claim, proof)
+	}
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+impl<T: Config, Call> DispatchQueue<OriginFor<T>, Call> for Pallet<T> {}
+
+impl<T: Config> PalletStorage for Pallet<T> {
+	fn storage(&self) -> Entries {
+		self.claims.entries()
+	}
+
+	fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		self.claims.restore(storage)
+	}
 }
 
 #[cfg(test)]
 mod test {
+	use super::{Error, Event};
+	use crate::support::{DispatchError, DispatchResult, Origin};
+
 	struct TestConfig;
 
 	impl super::Config for TestConfig {
-		type Content = &'static str;
+		type Content = String;
 	}
 
 	impl crate::system::Config for TestConfig {
-		type AccountId = &'static str;
+		type AccountId = String;
 		type BlockNumber = u32;
 		type Nonce = u32;
+		type Backend = crate::storage::MemoryBackend;
+		type RuntimeEvent = ();
+		const MAX_BLOCK_WEIGHT: crate::support::Weight = u64::MAX;
+		const MAX_BLOCK_LENGTH: usize = usize::MAX;
+		const MAX_CALL_DEPTH: u32 = 4;
 	}
 
 	#[test]
 	fn basic_proof_of_existence() {
-        let mut poe = super::Pallet::<TestConfig>::new();
+        let mut poe = super::Pallet::<TestConfig>::new(&Default::default());
         
-        let _ = poe.create_claim("alice", "my_document");
-        assert_eq!(poe.get_claim(&"my_document"), Some(&"alice"));
+        let _ = poe.create_claim(Origin::Signed("alice".to_string()), "my_document".to_string());
+        assert_eq!(poe.get_claim(&"my_document".to_string()), Some("alice".to_string()));
 
-        let res: Result<(), &str> = poe.revoke_claim("bob", "my_document");
-        assert_eq!(res, Err("Caller is not the owner of the claim"));
+        let res: DispatchResult = poe.revoke_claim(Origin::Signed("bob".to_string()), "my_document".to_string());
+        assert_eq!(res, Err(Error::NotClaimOwner.into()));
 
-        let res = poe.create_claim("bob", "my_document");
-        assert_eq!(res, Err("this content is already claimed"));
+        let res = poe.create_claim(Origin::Signed("bob".to_string()), "my_document".to_string());
+        assert_eq!(res, Err(Error::AlreadyClaimed.into()));
 
         // Ok(())
-        let res = poe.revoke_claim("alice", "my_document");
+        let res = poe.revoke_claim(Origin::Signed("alice".to_string()), "my_document".to_string());
         assert_eq!(res, Ok(()));
-        assert_eq!(poe.get_claim(&"alice"), None);
+        assert_eq!(poe.get_claim(&"alice".to_string()), None);
+        assert_eq!(
+            poe.take_events(),
+            vec![
+                Event::ClaimCreated { owner: "alice".to_string(), claim: "my_document".to_string() },
+                Event::ClaimRevoked { owner: "alice".to_string(), claim: "my_document".to_string() },
+            ]
+        );
 
         // claim does not exist
-        let res = poe.revoke_claim("alice", "no_document_existencee");
-        assert_eq!(res, Err("claim does not exist"));
+        let res = poe.revoke_claim(Origin::Signed("alice".to_string()), "no_document_existencee".to_string());
+        assert_eq!(res, Err(Error::ClaimNotFound.into()));
         
         // claim does not exist
-        let res = poe.revoke_claim("alice", "my_document");
-        assert_eq!(res, Err("claim does not exist"));
+        let res = poe.revoke_claim(Origin::Signed("alice".to_string()), "my_document".to_string());
+        assert_eq!(res, Err(Error::ClaimNotFound.into()));
+
+        // claims belong to accounts, so they must be signed
+        let res = poe.create_claim(Origin::Root, "my_document".to_string());
+        assert_eq!(res, Err(DispatchError::BadOrigin));
 	}
-}
\ No newline at end of file
+}
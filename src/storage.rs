@@ -0,0 +1,431 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::codec::{self, Decode, Encode};
+use crate::merkle::{self, Proof};
+use crate::support::{hash, Hash};
+
+/// A key-value store over encoded keys and values, where the state of the pallets lives.
+pub trait Backend {
+	/// Get the value stored under `key`, if any.
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+	/// Store `value` under `key`, replacing the previous value.
+	fn put(&mut self, key: Vec<u8>, value: Vec<u8>);
+	/// Remove the value stored under `key`, if any.
+	fn delete(&mut self, key: &[u8]);
+	/// Get all the entries whose key starts with `prefix`, in key order.
+	fn iter_prefix(&self, prefix: &[u8]) -> Entries;
+	/// Start a transaction: the changes made from now on can be reverted together. Transactions can
+	/// be nested.
+	fn start_transaction(&mut self);
+	/// Keep the changes made since the innermost transaction started. If it is nested, they can
+	/// still be reverted along with the enclosing transaction.
+	fn commit_transaction(&mut self);
+	/// Revert the changes made since the innermost transaction started.
+	fn rollback_transaction(&mut self);
+}
+
+/// Storage entries, as encoded keys and values.
+pub type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A pallet whose state lives in storage items, so it can be saved and restored as raw entries.
+pub trait PalletStorage {
+	/// All the storage entries of the pallet.
+	fn storage(&self) -> Entries;
+	/// Replace the state of the pallet with the entries of `storage` which belong to its items.
+	/// Entries of other pallets are ignored. Fails if an entry does not decode as the type of its
+	/// item, and may then have restored some of the items already.
+	fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error>;
+}
+
+/// A backend which keeps everything in memory.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+	entries: BTreeMap<Vec<u8>, Vec<u8>>,
+	// For each open transaction, innermost last, the value each key it changed had before, if any.
+	journal: Vec<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl MemoryBackend {
+	// Remember the value under `key` before the innermost transaction first changes it.
+	fn record(&mut self, key: &[u8]) {
+		if let Some(changes) = self.journal.last_mut() {
+			changes.entry(key.to_vec()).or_insert_with(|| self.entries.get(key).cloned());
+		}
+	}
+}
+
+impl Backend for MemoryBackend {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.entries.get(key).cloned()
+	}
+
+	fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.record(&key);
+		self.entries.insert(key, value);
+	}
+
+	fn delete(&mut self, key: &[u8]) {
+		self.record(key);
+		self.entries.remove(key);
+	}
+
+	fn iter_prefix(&self, prefix: &[u8]) -> Entries {
+		self.entries
+			.range(prefix.to_vec()..)
+			.take_while(|(key, _)| key.starts_with(prefix))
+			.map(|(key, value)| (key.clone(), value.clone()))
+			.collect()
+	}
+
+	fn start_transaction(&mut self) {
+		self.journal.push(BTreeMap::new());
+	}
+
+	fn commit_transaction(&mut self) {
+		let changes = self.journal.pop().expect("a transaction is open");
+		// The enclosing transaction reverts to the values from before both of them.
+		if let Some(outer) = self.journal.last_mut() {
+			for (key, original) in changes {
+				outer.entry(key).or_insert(original);
+			}
+		}
+	}
+
+	fn rollback_transaction(&mut self) {
+		let changes = self.journal.pop().expect("a transaction is open");
+		for (key, original) in changes {
+			match original {
+				Some(value) => self.entries.insert(key, value),
+				None => self.entries.remove(&key),
+			};
+		}
+	}
+}
+
+/// A handle on the backend of a runtime. The storage items of every pallet are views over the
+/// same backend, so cloning a handle shares the backend rather than copying it.
+pub struct Storage<B>(Rc<RefCell<B>>);
+
+impl<B: Backend> Storage<B> {
+	/// Start a transaction on the backend, see `Backend::start_transaction`.
+	pub fn start_transaction(&self) {
+		self.0.borrow_mut().start_transaction();
+	}
+
+	/// Keep the changes of the innermost transaction, see `Backend::commit_transaction`.
+	pub fn commit_transaction(&self) {
+		self.0.borrow_mut().commit_transaction();
+	}
+
+	/// Revert the changes of the innermost transaction, see `Backend::rollback_transaction`.
+	pub fn rollback_transaction(&self) {
+		self.0.borrow_mut().rollback_transaction();
+	}
+}
+
+impl<B: Default> Default for Storage<B> {
+	fn default() -> Self {
+		Self(Rc::new(RefCell::new(B::default())))
+	}
+}
+
+impl<B> Clone for Storage<B> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone())
+	}
+}
+
+// The content of the backend is printed by the storage items.
+impl<B> core::fmt::Debug for Storage<B> {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.write_str("Storage")
+	}
+}
+
+// The keys of a storage item start with the hash of the name of its pallet, followed by the hash of
+// its own name, so the items of every pallet can share a key space.
+fn storage_prefix(pallet: &str, item: &str) -> Vec<u8> {
+	let mut prefix = hash(pallet.as_bytes())[..16].to_vec();
+	prefix.extend_from_slice(&hash(item.as_bytes())[..16]);
+	prefix
+}
+
+// Values are only written through the typed wrappers, or restored after checking that they
+// decode, so they always decode.
+fn decode<T: Decode>(encoded: &[u8]) -> T {
+	codec::decode_all(encoded).expect("storage only holds values of the right type")
+}
+
+/// A single value of type `V` in storage.
+pub struct StorageValue<V, B> {
+	key: Vec<u8>,
+	storage: Storage<B>,
+	_value: PhantomData<V>,
+}
+
+impl<V: Encode + Decode, B: Backend> StorageValue<V, B> {
+	/// The value of the item named `item` of the pallet named `pallet`, in `storage`.
+	pub fn new(storage: &Storage<B>, pallet: &str, item: &str) -> Self {
+		Self { key: storage_prefix(pallet, item), storage: storage.clone(), _value: PhantomData }
+	}
+
+	/// Get the value, if it was ever set.
+	pub fn get(&self) -> Option<V> {
+		self.storage.0.borrow().get(&self.key).map(|encoded| decode(&encoded))
+	}
+
+	/// Set the value.
+	pub fn put(&mut self, value: &V) {
+		self.storage.0.borrow_mut().put(self.key.clone(), value.encode());
+	}
+
+	/// The raw storage entry of the value, if it was ever set.
+	pub fn entries(&self) -> Entries {
+		let value = self.storage.0.borrow().get(&self.key);
+		value.map(|value| (self.key.clone(), value)).into_iter().collect()
+	}
+
+	/// Replace the value with the entry of `storage` under its key, if any. Fails without changing
+	/// anything if that entry does not decode as a `V`.
+	pub fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		let entries = storage.iter().filter(|(key, _)| *key == self.key).collect::<Vec<_>>();
+		for (_, value) in &entries {
+			codec::decode_all::<V>(value)?;
+		}
+		let mut backend = self.storage.0.borrow_mut();
+		backend.delete(&self.key);
+		for (key, value) in entries {
+			backend.put(key.clone(), value.clone());
+		}
+		Ok(())
+	}
+}
+
+impl<V: Encode + Decode + core::fmt::Debug, B: Backend> core::fmt::Debug for StorageValue<V, B> {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.debug_tuple("StorageValue").field(&self.get()).finish()
+	}
+}
+
+// A value is written as itself, or `null` if it was never set.
+impl<V: Encode + Decode + Serialize, B: Backend> Serialize for StorageValue<V, B> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.get().serialize(serializer)
+	}
+}
+
+/// A map from keys of type `K` to values of type `V` in storage.
+pub struct StorageMap<K, V, B> {
+	prefix: Vec<u8>,
+	storage: Storage<B>,
+	_entry: PhantomData<(K, V)>,
+}
+
+impl<K: Encode + Decode, V: Encode + Decode, B: Backend> StorageMap<K, V, B> {
+	/// The map of the item named `item` of the pallet named `pallet`, in `storage`.
+	pub fn new(storage: &Storage<B>, pallet: &str, item: &str) -> Self {
+		Self { prefix: storage_prefix(pallet, item), storage: storage.clone(), _entry: PhantomData }
+	}
+
+	/// The key in the backend of the entry for `key`: the prefix of the map followed by the encoded
+	/// `key`.
+	pub fn storage_key(&self, key: &K) -> Vec<u8> {
+		let mut storage_key = self.prefix.clone();
+		key.encode_to(&mut storage_key);
+		storage_key
+	}
+
+	/// Get the value stored under `key`, if any.
+	pub fn get(&self, key: &K) -> Option<V> {
+		self.storage.0.borrow().get(&self.storage_key(key)).map(|encoded| decode(&encoded))
+	}
+
+	/// Store `value` under `key`.
+	pub fn insert(&mut self, key: &K, value: &V) {
+		self.storage.0.borrow_mut().put(self.storage_key(key), value.encode());
+	}
+
+	/// Remove the value stored under `key`, if any.
+	pub fn remove(&mut self, key: &K) {
+		self.storage.0.borrow_mut().delete(&self.storage_key(key));
+	}
+
+	/// Get all the entries of the map, in the order of their encoded keys.
+	pub fn iter(&self) -> Vec<(K, V)> {
+		self.entries()
+			.into_iter()
+			.map(|(key, value)| (decode(&key[self.prefix.len()..]), decode(&value)))
+			.collect()
+	}
+
+	/// Check that `proof` proves the entry for `key` is in a state whose root is `state_root`, and
+	/// return its value.
+	pub fn verify(&self, state_root: &Hash, key: &K, proof: &Proof) -> Option<V> {
+		if proof.key != self.storage_key(key) || !merkle::verify(state_root, proof) {
+			return None;
+		}
+		codec::decode_all(&proof.value).ok()
+	}
+
+	/// The raw storage entries of the map, in key order.
+	pub fn entries(&self) -> Entries {
+		self.storage.0.borrow().iter_prefix(&self.prefix)
+	}
+
+	/// Replace the content of the map with the entries of `storage` under its prefix. Fails without
+	/// changing anything if one of those entries does not decode as a `K` and a `V`.
+	pub fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		let entries =
+			storage.iter().filter(|(key, _)| key.starts_with(&self.prefix)).collect::<Vec<_>>();
+		for (key, value) in &entries {
+			codec::decode_all::<K>(&key[self.prefix.len()..])?;
+			codec::decode_all::<V>(value)?;
+		}
+		let mut backend = self.storage.0.borrow_mut();
+		for (key, _) in backend.iter_prefix(&self.prefix) {
+			backend.delete(&key);
+		}
+		for (key, value) in entries {
+			backend.put(key.clone(), value.clone());
+		}
+		Ok(())
+	}
+}
+
+impl<K, V, B> core::fmt::Debug for StorageMap<K, V, B>
+where
+	K: Encode + Decode + core::fmt::Debug,
+	V: Encode + Decode + core::fmt::Debug,
+	B: Backend,
+{
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.debug_map().entries(self.iter()).finish()
+	}
+}
+
+// A map is written as a map from its keys to its values.
+impl<K, V, B> Serialize for StorageMap<K, V, B>
+where
+	K: Encode + Decode + Serialize,
+	V: Encode + Decode + Serialize,
+	B: Backend,
+{
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let entries = self.iter();
+		let mut map = serializer.serialize_map(Some(entries.len()))?;
+		for (key, value) in &entries {
+			map.serialize_entry(key, value)?;
+		}
+		map.end()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Backend, MemoryBackend, Storage, StorageMap, StorageValue};
+	use crate::codec::Encode;
+
+	#[test]
+	fn memory_backend() {
+		let mut backend = MemoryBackend::default();
+		backend.put(vec![1, 1], vec![10]);
+		backend.put(vec![1, 2], vec![20]);
+		backend.put(vec![2, 1], vec![30]);
+		assert_eq!(backend.get(&[1, 2]), Some(vec![20]));
+
+		backend.delete(&[1, 2]);
+		assert_eq!(backend.get(&[1, 2]), None);
+		assert_eq!(backend.iter_prefix(&[1]), vec![(vec![1, 1], vec![10])]);
+		assert_eq!(backend.iter_prefix(&[]).len(), 2);
+	}
+
+	#[test]
+	fn transactions() {
+		let mut backend = MemoryBackend::default();
+		backend.put(vec![1], vec![10]);
+		backend.put(vec![2], vec![20]);
+
+		backend.start_transaction();
+		backend.put(vec![1], vec![11]);
+		backend.delete(&[2]);
+		backend.rollback_transaction();
+		assert_eq!(backend.iter_prefix(&[]), vec![(vec![1], vec![10]), (vec![2], vec![20])]);
+
+		// The changes of a committed transaction are reverted with the enclosing transaction.
+		backend.start_transaction();
+		backend.put(vec![1], vec![11]);
+		backend.start_transaction();
+		backend.put(vec![1], vec![12]);
+		backend.put(vec![3], vec![30]);
+		backend.commit_transaction();
+		backend.start_transaction();
+		backend.delete(&[2]);
+		backend.rollback_transaction();
+		assert_eq!(backend.get(&[1]), Some(vec![12]));
+		assert_eq!(backend.get(&[2]), Some(vec![20]));
+		backend.rollback_transaction();
+		assert_eq!(backend.iter_prefix(&[]), vec![(vec![1], vec![10]), (vec![2], vec![20])]);
+
+		backend.start_transaction();
+		backend.put(vec![3], vec![30]);
+		backend.commit_transaction();
+		assert_eq!(backend.get(&[3]), Some(vec![30]));
+	}
+
+	#[test]
+	fn typed_storage() {
+		let storage = Storage::<MemoryBackend>::default();
+		let mut value = StorageValue::<u32, _>::new(&storage, "System", "BlockNumber");
+		assert_eq!(value.get(), None);
+		value.put(&7);
+		assert_eq!(value.get(), Some(7));
+
+		let mut map = StorageMap::<String, u128, _>::new(&storage, "Balances", "Balances");
+		map.insert(&"bob".to_string(), &20);
+		map.insert(&"alice".to_string(), &10);
+		assert_eq!(map.get(&"alice".to_string()), Some(10));
+		assert_eq!(map.iter(), vec![("bob".to_string(), 20), ("alice".to_string(), 10)]);
+
+		map.remove(&"bob".to_string());
+		assert_eq!(map.get(&"bob".to_string()), None);
+		assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"alice":10}"#);
+
+		// Both items are views over the same backend, and so are their copies.
+		let same_value = StorageValue::<u32, _>::new(&storage.clone(), "System", "BlockNumber");
+		assert_eq!(same_value.get(), Some(7));
+		storage.start_transaction();
+		value.put(&8);
+		map.insert(&"alice".to_string(), &11);
+		storage.rollback_transaction();
+		assert_eq!(same_value.get(), Some(7));
+		assert_eq!(map.get(&"alice".to_string()), Some(10));
+	}
+
+	#[test]
+	fn restore_checks_types() {
+		let storage = Storage::<MemoryBackend>::default();
+		let mut map = StorageMap::<String, u128, _>::new(&storage, "Balances", "Balances");
+		map.insert(&"alice".to_string(), &10);
+		let mut entries = map.entries();
+		assert_eq!(map.restore(&entries), Ok(()));
+		assert_eq!(map.iter(), vec![("alice".to_string(), 10)]);
+
+		// A value which is not a `u128` is rejected, and the map is left as it was.
+		entries.push((map.storage_key(&"bob".to_string()), vec![1, 2, 3]));
+		assert!(map.restore(&entries).is_err());
+		assert_eq!(map.iter(), vec![("alice".to_string(), 10)]);
+
+		let mut value = StorageValue::<u32, _>::new(&storage, "System", "BlockNumber");
+		value.put(&6);
+		let key = value.entries()[0].0.clone();
+		assert!(value.restore(&[(key.clone(), vec![1])]).is_err());
+		assert_eq!(value.get(), Some(6));
+		assert_eq!(value.restore(&[(key, 7u32.encode())]), Ok(()));
+		assert_eq!(value.get(), Some(7));
+	}
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::codec::{self, Decode, Encode};
+use crate::storage::Entries;
+
+/// A store on disk for the state of the runtime and the blocks it imported, so a node can stop and
+/// start again where it left off. It is a plain directory:
+///
+/// - `state` - the storage entries of the runtime after the last imported block.
+/// - `blocks/<number>` - each imported block, encoded.
+/// - `pool` - the transactions submitted to the node which are not in a block yet, encoded.
+///
+/// Files are never modified in place. They are written to a temporary file, flushed to disk, then
+/// renamed over the old version, so a crash leaves either the old or the new version of a file,
+/// never a mix. A block is written before the state which includes it, so the state is the commit
+/// point: if a node crashes in between, it restarts before that block, and can import it again.
+pub struct Store {
+	dir: PathBuf,
+}
+
+/// The errors which can happen when reading or writing a store.
+#[derive(Debug)]
+pub enum Error {
+	/// A file of the store could not be read or written.
+	Io(std::io::Error),
+	/// A file of the store does not hold what it should.
+	Codec(codec::Error),
+	/// The store holds the state of a chain with another genesis block.
+	GenesisMismatch,
+}
+
+impl core::fmt::Display for Error {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "cannot access store: {}", e),
+			Error::Codec(e) => write!(f, "corrupted store: {}", e),
+			Error::GenesisMismatch => write!(f, "the store belongs to another chain"),
+		}
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(e: std::io::Error) -> Self {
+		Error::Io(e)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(e: codec::Error) -> Self {
+		Error::Codec(e)
+	}
+}
+
+impl Store {
+	/// Open the store in the directory `dir`, creating it if needed.
+	pub fn open(dir: &Path) -> Result<Self, Error> {
+		fs::create_dir_all(dir.join("blocks"))?;
+		Ok(Self { dir: dir.to_path_buf() })
+	}
+
+	/// The state after the last imported block, or `None` if nothing was saved yet.
+	pub fn state(&self) -> Result<Option<Entries>, Error> {
+		self.read(&self.dir.join("state"))
+	}
+
+	/// Replace the saved state, for example with the genesis state of a new chain.
+	pub fn save_state(&self, state: &Entries) -> Result<(), Error> {
+		write_atomic(&self.dir.join("state"), &state.encode())
+	}
+
+	/// The imported block with the number `number`, if any.
+	pub fn block<B: Decode>(&self, number: u64) -> Result<Option<B>, Error> {
+		self.read(&self.block_path(number))
+	}
+
+	/// Record that the block `block` with the number `number` was imported, resulting in `state`.
+	pub fn import_block<B: Encode>(&self, number: u64, block: &B, state: &Entries) -> Result<(), Error> {
+		write_atomic(&self.block_path(number), &block.encode())?;
+		self.save_state(state)
+	}
+
+	/// The transactions saved with `save_pool`, or none if nothing was saved yet.
+	pub fn pool<E: Decode>(&self) -> Result<Vec<E>, Error> {
+		Ok(self.read(&self.dir.join("pool"))?.unwrap_or_default())
+	}
+
+	/// Replace the saved transactions of the pool.
+	pub fn save_pool<E: Encode>(&self, transactions: &[E]) -> Result<(), Error> {
+		write_atomic(&self.dir.join("pool"), &transactions.encode())
+	}
+
+	fn block_path(&self, number: u64) -> PathBuf {
+		self.dir.join("blocks").join(number.to_string())
+	}
+
+	fn read<T: Decode>(&self, path: &Path) -> Result<Option<T>, Error> {
+		match fs::read(path) {
+			Ok(bytes) => Ok(Some(codec::decode_all(&bytes)?)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+}
+
+// Replace the content of the file at `path` with `bytes`, so that a crash leaves either the old or
+// the new content.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+	let tmp = path.with_extension("tmp");
+	let mut file = fs::File::create(&tmp)?;
+	file.write_all(bytes)?;
+	file.sync_all()?;
+	fs::rename(&tmp, path)?;
+	// The rename itself is only durable once the directory is flushed.
+	if let Some(dir) = path.parent() {
+		fs::File::open(dir)?.sync_all()?;
+	}
+	Ok(())
+}
+
+/// A fresh directory for a test, so tests do not see each other's files. `name` must be unique
+/// among the tests of the crate.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	dir
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{test_dir, Error, Store};
+
+	#[test]
+	fn save_and_reopen() {
+		let dir = test_dir("store-save_and_reopen");
+		let store = Store::open(&dir).unwrap();
+		assert!(store.state().unwrap().is_none());
+		assert!(store.block::<u32>(1).unwrap().is_none());
+		assert!(store.pool::<u32>().unwrap().is_empty());
+
+		store.save_state(&vec![(vec![1], vec![10])]).unwrap();
+		store.import_block(1, &7u32, &vec![(vec![1], vec![20])]).unwrap();
+
+		let store = Store::open(&dir).unwrap();
+		assert_eq!(store.state().unwrap(), Some(vec![(vec![1], vec![20])]));
+		assert_eq!(store.block::<u32>(1).unwrap(), Some(7));
+		store.save_pool(&[3u32, 4]).unwrap();
+		assert_eq!(store.pool::<u32>().unwrap(), vec![3, 4]);
+
+		// A write interrupted by a crash leaves a temporary file behind, which is ignored.
+		std::fs::write(dir.join("state.tmp"), [0xff]).unwrap();
+		assert_eq!(store.state().unwrap(), Some(vec![(vec![1], vec![20])]));
+
+		std::fs::write(dir.join("state"), [0xff]).unwrap();
+		assert!(matches!(store.state(), Err(Error::Codec(_))));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,290 @@
+use core::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec;
+use crate::storage::{Entries, PalletStorage, Storage, StorageValue};
+use crate::support::{
+	ensure_signed, DispatchQueue, DispatchResult, Hooks, Origin, PalletError, Weight,
+};
+use crate::system::OriginFor;
+
+pub trait Config: crate::system::Config {
+	/// The aggregated call type of the runtime, which the sudo key can dispatch.
+	type RuntimeCall: Debug + Clone;
+
+	/// The weight of a call of the runtime, which is added to the weight of the sudo call making it.
+	fn call_weight(call: &Self::RuntimeCall) -> Weight;
+}
+
+/// This is the Sudo Pallet.
+/// It lets a single key account make any call with the `Root` origin, on behalf of any account, or
+/// with the `None` origin.
+#[derive(Debug, Serialize)]
+#[serde(bound(serialize = "T::AccountId: Serialize"))]
+pub struct Pallet<T: Config> {
+	/// The account which can use this pallet, if any.
+	key: StorageValue<T::AccountId, T::Backend>,
+	/// Events deposited by this pallet which have not been collected by the runtime yet.
+	#[serde(skip)]
+	events: Vec<Event<T>>,
+	/// Calls requested by the key which have not been dispatched by the runtime yet, along with the
+	/// origin to dispatch them with.
+	#[serde(skip)]
+	dispatches: Vec<(OriginFor<T>, T::RuntimeCall)>,
+}
+
+/// The events which can be deposited by the Sudo Pallet.
+#[macros::event]
+pub enum Event<T: Config> {
+	/// The sudo key was changed from `old` to `new`.
+	KeyChanged { old: T::AccountId, new: T::AccountId },
+	/// A call made by the sudo key with the `Root` origin was dispatched, with this result.
+	Sudid { result: DispatchResult },
+	/// A call made by the sudo key on behalf of an account was dispatched, with this result.
+	SudoAsDone { result: DispatchResult },
+	/// A call made by the sudo key with the `None` origin was dispatched, with this result.
+	SudoUnsignedDone { result: DispatchResult },
+}
+
+/// The errors which can be returned by the Sudo Pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The call was not made by the sudo key.
+	RequireSudo,
+}
+
+impl PalletError for Error {
+	const VARIANTS: &'static [Self] = &[Error::RequireSudo];
+
+	fn index(&self) -> u8 {
+		*self as u8
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Error::RequireSudo => "RequireSudo",
+		}
+	}
+
+	fn docs(&self) -> &'static str {
+		match self {
+			Error::RequireSudo => "The call was not made by the sudo key.",
+		}
+	}
+}
+
+/// The initial state of the Sudo Pallet.
+#[derive(Serialize, Deserialize)]
+#[serde(
+	bound(serialize = "T::AccountId: Serialize", deserialize = "T::AccountId: Deserialize<'de>"),
+	default,
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The sudo key, if any.
+	pub key: Option<T::AccountId>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { key: None }
+	}
+}
+
+impl<T: Config> GenesisConfig<T> {
+	/// Build the Sudo Pallet with this initial state, in `storage`.
+	pub fn build(self, storage: &Storage<T::Backend>) -> Pallet<T> {
+		let mut pallet = Pallet::new(storage);
+		if let Some(key) = self.key {
+			pallet.key.put(&key);
+		}
+		pallet
+	}
+}
+
+// The calls have to be boxed in the `Call` enum, which would otherwise contain itself.
+#[allow(clippy::boxed_local)]
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Dispatch `call` with the `Root` origin.
+	/// Only the sudo key can make this call. It succeeds even if `call` fails: the result of `call`
+	/// is in the `Sudid` event.
+	#[weight(100 + T::call_weight(call))]
+	pub fn sudo(&mut self, origin: OriginFor<T>, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_key(origin)?;
+		self.dispatches.push((Origin::Root, *call));
+		Ok(())
+	}
+
+	/// Make `new` the sudo key, instead of the caller.
+	/// Only the sudo key can make this call.
+	#[weight(100)]
+	pub fn set_key(&mut self, origin: OriginFor<T>, new: T::AccountId) -> DispatchResult {
+		let old = self.ensure_key(origin)?;
+		self.key.put(&new);
+		self.deposit_event(Event::KeyChanged { old, new });
+		Ok(())
+	}
+
+	/// Dispatch `call` on behalf of `who`, with their `Signed` origin.
+	/// Only the sudo key can make this call. It succeeds even if `call` fails: the result of `call`
+	/// is in the `SudoAsDone` event.
+	#[weight(100 + T::call_weight(call))]
+	pub fn sudo_as(
+		&mut self,
+		origin: OriginFor<T>,
+		who: T::AccountId,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		self.ensure_key(origin)?;
+		self.dispatches.push((Origin::Signed(who), *call));
+		Ok(())
+	}
+
+	/// Dispatch `call` with the `None` origin, as if nobody made it.
+	/// Only the sudo key can make this call. It succeeds even if `call` fails: the result of `call`
+	/// is in the `SudoUnsignedDone` event.
+	#[weight(100 + T::call_weight(call))]
+	pub fn sudo_unsigned(&mut self, origin: OriginFor<T>, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_key(origin)?;
+		self.dispatches.push((Origin::None, *call));
+		Ok(())
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Sudo Pallet, without a key.
+	pub fn new(storage: &Storage<T::Backend>) -> Self {
+		Self {
+			key: StorageValue::new(storage, "Sudo", "Key"),
+			events: Vec::new(),
+			dispatches: Vec::new(),
+		}
+	}
+
+	/// Get the sudo key, if any.
+	pub fn key(&self) -> Option<T::AccountId> {
+		self.key.get()
+	}
+
+	/// Check that the call was signed by the sudo key, and return the key.
+	fn ensure_key(&self, origin: OriginFor<T>) -> Result<T::AccountId, crate::support::DispatchError> {
+		let who = ensure_signed(origin)?;
+		if self.key.get().as_ref() != Some(&who) {
+			return Err(Error::RequireSudo.into());
+		}
+		Ok(who)
+	}
+
+	/// Deposit an event, to be collected by the runtime at the end of the current extrinsic.
+	fn deposit_event(&mut self, event: Event<T>) {
+		self.events.push(event);
+	}
+
+	/// Take all the events deposited since the last time this was called.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		core::mem::take(&mut self.events)
+	}
+
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+// The calls requested by the key are dispatched by the runtime, which reports their result back.
+impl<T: Config> DispatchQueue<OriginFor<T>, T::RuntimeCall> for Pallet<T> {
+	fn take_dispatches(&mut self) -> Vec<(OriginFor<T>, T::RuntimeCall)> {
+		core::mem::take(&mut self.dispatches)
+	}
+
+	fn dispatched(&mut self, origin: OriginFor<T>, result: DispatchResult) {
+		match origin {
+			Origin::Root => self.deposit_event(Event::Sudid { result }),
+			Origin::Signed(_) => self.deposit_event(Event::SudoAsDone { result }),
+			Origin::None => self.deposit_event(Event::SudoUnsignedDone { result }),
+		}
+	}
+}
+
+impl<T: Config> PalletStorage for Pallet<T> {
+	fn storage(&self) -> Entries {
+		self.key.entries()
+	}
+
+	fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		self.key.restore(storage)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Call, Error, Event};
+	use crate::support::{DispatchError, DispatchQueue, Origin};
+
+	struct TestConfig;
+
+	impl super::Config for TestConfig {
+		type RuntimeCall = &'static str;
+
+		fn call_weight(_call: &&'static str) -> crate::support::Weight {
+			10
+		}
+	}
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Backend = crate::storage::MemoryBackend;
+		type RuntimeEvent = ();
+		const MAX_BLOCK_WEIGHT: crate::support::Weight = u64::MAX;
+		const MAX_BLOCK_LENGTH: usize = usize::MAX;
+		const MAX_CALL_DEPTH: u32 = 4;
+	}
+
+	#[test]
+	fn only_the_key_can_dispatch() {
+		let mut sudo = super::Pallet::<TestConfig>::new(&Default::default());
+		assert_eq!(sudo.sudo(Origin::Signed("alice".to_string()), Box::new("call")), Err(Error::RequireSudo.into()));
+
+		let mut sudo = super::GenesisConfig::<TestConfig> { key: Some("alice".to_string()) }.build(&Default::default());
+		assert_eq!(sudo.sudo(Origin::Signed("bob".to_string()), Box::new("call")), Err(Error::RequireSudo.into()));
+		assert_eq!(sudo.sudo(Origin::Root, Box::new("call")), Err(DispatchError::BadOrigin));
+		assert!(sudo.take_dispatches().is_empty());
+
+		assert_eq!(sudo.sudo(Origin::Signed("alice".to_string()), Box::new("first")), Ok(()));
+		assert_eq!(sudo.sudo_as(Origin::Signed("alice".to_string()), "bob".to_string(), Box::new("second")), Ok(()));
+		assert_eq!(sudo.sudo_unsigned(Origin::Signed("alice".to_string()), Box::new("third")), Ok(()));
+		assert_eq!(
+			sudo.take_dispatches(),
+			vec![(Origin::Root, "first"), (Origin::Signed("bob".to_string()), "second"), (Origin::None, "third")]
+		);
+		assert!(sudo.take_events().is_empty());
+
+		// The runtime reports the result of each call back.
+		sudo.dispatched(Origin::Root, Ok(()));
+		sudo.dispatched(Origin::Signed("bob".to_string()), Err(DispatchError::BadOrigin));
+		sudo.dispatched(Origin::None, Ok(()));
+		assert_eq!(
+			sudo.take_events(),
+			vec![
+				Event::Sudid { result: Ok(()) },
+				Event::SudoAsDone { result: Err(DispatchError::BadOrigin) },
+				Event::SudoUnsignedDone { result: Ok(()) },
+			]
+		);
+		assert_eq!(Call::<TestConfig>::sudo { call: Box::new("first") }.weight(), 110);
+	}
+
+	#[test]
+	fn change_the_key() {
+		let mut sudo = super::GenesisConfig::<TestConfig> { key: Some("alice".to_string()) }.build(&Default::default());
+
+		assert_eq!(sudo.set_key(Origin::Signed("bob".to_string()), "bob".to_string()), Err(Error::RequireSudo.into()));
+		assert_eq!(sudo.set_key(Origin::Signed("alice".to_string()), "bob".to_string()), Ok(()));
+		assert_eq!(sudo.key(), Some("bob".to_string()));
+		assert_eq!(sudo.take_events(), vec![Event::KeyChanged { old: "alice".to_string(), new: "bob".to_string() }]);
+
+		assert_eq!(sudo.sudo(Origin::Signed("alice".to_string()), Box::new("call")), Err(Error::RequireSudo.into()));
+	}
+}
@@ -1,4 +1,13 @@
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::codec::{self, Decode, Encode};
+use crate::metadata::TypeInfo;
+use crate::storage::{Backend, Storage};
+
 /// The most primitive representation of a Blockchain block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block<Header, Extrinsic> {
 	/// The block header contains metadata about the block.
 	pub header: Header,
@@ -6,37 +15,569 @@ pub struct Block<Header, Extrinsic> {
 	pub extrinsics: Vec<Extrinsic>,
 }
 
-/// We are using an extremely simplified header which only contains the current block number.
-/// On a real blockchain, you would expect to also find:
-/// - parent block hash
-/// - state root
-/// - extrinsics root
-/// - etc...
+impl<Header: Encode, Extrinsic: Encode> Encode for Block<Header, Extrinsic> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.header.encode_to(dest);
+		self.extrinsics.encode_to(dest);
+	}
+}
+
+impl<Header: Decode, Extrinsic: Decode> Decode for Block<Header, Extrinsic> {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Ok(Self { header: Decode::decode(input)?, extrinsics: Decode::decode(input)? })
+	}
+}
+
+/// The header of a block. It commits to the previous block, to the extrinsics of this block and to
+/// the state after executing them, so the hash of a header identifies the whole chain up to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Header<BlockNumber> {
+	/// The number of this block. The first block after genesis is number 1.
 	pub block_number: BlockNumber,
+	/// The hash of the header of the previous block. For the first block, this is the genesis hash.
+	#[serde(with = "hex")]
+	pub parent_hash: Hash,
+	/// The hash of the runtime state after executing this block.
+	#[serde(with = "hex")]
+	pub state_root: Hash,
+	/// The hash of the list of extrinsics of this block. See `extrinsics_root`.
+	#[serde(with = "hex")]
+	pub extrinsics_root: Hash,
+}
+
+impl<BlockNumber: Encode> Header<BlockNumber> {
+	/// The hash of the encoded header, which is also the hash of the block.
+	pub fn hash(&self) -> Hash {
+		hash(&self.encode())
+	}
+}
+
+impl<BlockNumber: Encode> Encode for Header<BlockNumber> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.block_number.encode_to(dest);
+		self.parent_hash.encode_to(dest);
+		self.state_root.encode_to(dest);
+		self.extrinsics_root.encode_to(dest);
+	}
+}
+
+impl<BlockNumber: Decode> Decode for Header<BlockNumber> {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Ok(Self {
+			block_number: Decode::decode(input)?,
+			parent_hash: Decode::decode(input)?,
+			state_root: Decode::decode(input)?,
+			extrinsics_root: Decode::decode(input)?,
+		})
+	}
+}
+
+/// The hash of the encoded list of extrinsics of a block.
+pub fn extrinsics_root<Extrinsic: Encode>(extrinsics: &[Extrinsic]) -> Hash {
+	hash(&extrinsics.encode())
+}
+
+/// What happened when a block was executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockReceipt<AccountId, Event> {
+	/// The receipt of each extrinsic of the block, in order.
+	pub extrinsics: Vec<ExtrinsicReceipt<AccountId, Event>>,
+}
+
+/// What happened when an extrinsic of a block was applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtrinsicReceipt<AccountId, Event> {
+	/// The index of the extrinsic in its block.
+	pub index: u32,
+	/// Who signed the extrinsic, and paid its fee.
+	pub caller: AccountId,
+	/// The outcome of the call of the extrinsic. A failed call is still part of the block, but
+	/// everything it did was reverted.
+	pub result: DispatchResult,
+	/// The weight the extrinsic counted toward the block.
+	pub weight: Weight,
+	/// The events deposited while applying the extrinsic, including the fee payment.
+	pub events: Vec<Event>,
 }
 
 /// This is an "extrinsic": literally an external message from outside of the blockchain.
-/// This simplified version of an extrinsic tells us who is making the call, and which call they are
-/// making.
-pub struct Extrinsic<Caller, Call> {
+/// This simplified version of an extrinsic tells us who is making the call, which call they are
+/// making, and the nonce of the caller it is meant for. An extrinsic is only valid with the next
+/// nonce of its caller, so it cannot be executed twice.
+///
+/// The caller signs the call, the nonce and the genesis hash of the chain, so nobody else can make
+/// calls on their behalf, and the extrinsic cannot be replayed on another chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Extrinsic<Caller, Nonce, Call> {
 	pub caller: Caller,
+	pub nonce: Nonce,
 	pub call: Call,
+	pub signature: Signature,
+}
+
+impl<Nonce: Encode, Call: Encode> Extrinsic<PublicKey, Nonce, Call> {
+	/// Create an extrinsic making `call` on behalf of the owner of `pair`.
+	pub fn new_signed(pair: &Pair, nonce: Nonce, call: Call, genesis_hash: Hash) -> Self {
+		let signature = pair.sign(&signing_payload(&nonce, &call, &genesis_hash));
+		Self { caller: pair.public(), nonce, call, signature }
+	}
+
+	/// Check that the extrinsic was signed by its caller, for the chain with `genesis_hash`.
+	pub fn verify(&self, genesis_hash: &Hash) -> bool {
+		self.caller
+			.verify(&signing_payload(&self.nonce, &self.call, genesis_hash), &self.signature)
+	}
+}
+
+impl<Caller: Encode, Nonce: Encode, Call: Encode> Encode for Extrinsic<Caller, Nonce, Call> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.caller.encode_to(dest);
+		self.nonce.encode_to(dest);
+		self.call.encode_to(dest);
+		self.signature.encode_to(dest);
+	}
+}
+
+impl<Caller: Decode, Nonce: Decode, Call: Decode> Decode for Extrinsic<Caller, Nonce, Call> {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Ok(Self {
+			caller: Decode::decode(input)?,
+			nonce: Decode::decode(input)?,
+			call: Decode::decode(input)?,
+			signature: Decode::decode(input)?,
+		})
+	}
+}
+
+/// The message signed by the caller of an extrinsic: the encoded call, nonce and genesis hash.
+fn signing_payload<Nonce: Encode, Call: Encode>(
+	nonce: &Nonce,
+	call: &Call,
+	genesis_hash: &Hash,
+) -> Vec<u8> {
+	(call, nonce, genesis_hash).encode()
+}
+
+/// An ed25519 public key. The runtime uses it as the account id, so an account is controlled by
+/// whoever holds the matching private key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PublicKey(#[serde(with = "hex")] pub [u8; 32]);
+
+impl PublicKey {
+	/// Check that `signature` is a signature of `message` by the owner of this key.
+	///
+	/// This uses the strict checks of ed25519, which reject weak keys and signatures which could
+	/// be changed into another valid signature of the same message.
+	pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+		let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&self.0) else { return false };
+		key.verify_strict(message, &ed25519_dalek::Signature::from_bytes(&signature.0)).is_ok()
+	}
+}
+
+impl Encode for PublicKey {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for PublicKey {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Ok(Self(Decode::decode(input)?))
+	}
+}
+
+impl TypeInfo for PublicKey {
+	fn type_name() -> String {
+		"PublicKey".to_string()
+	}
+}
+
+impl core::fmt::Debug for PublicKey {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		write!(f, "{}", hex::encode(&self.0))
+	}
+}
+
+/// An ed25519 signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Signature(#[serde(with = "hex")] pub [u8; 64]);
+
+impl Encode for Signature {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for Signature {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Ok(Self(Decode::decode(input)?))
+	}
+}
+
+/// An ed25519 key pair, which can sign messages for its public key.
+#[derive(Clone)]
+pub struct Pair(ed25519_dalek::SigningKey);
+
+impl Pair {
+	/// Create the key pair for a 32-byte secret seed. The same seed always gives the same pair.
+	pub fn from_seed(seed: &[u8; 32]) -> Self {
+		Self(ed25519_dalek::SigningKey::from_bytes(seed))
+	}
+
+	/// The public key of this pair.
+	pub fn public(&self) -> PublicKey {
+		PublicKey(self.0.verifying_key().to_bytes())
+	}
+
+	/// Sign `message` with this pair.
+	pub fn sign(&self, message: &[u8]) -> Signature {
+		Signature(self.0.sign(message).to_bytes())
+	}
+}
+
+/// The output of our hashing function: a 256-bit SHA-256 digest.
+pub type Hash = [u8; 32];
+
+/// Fixed-size byte arrays, like hashes, keys and signatures, written as `0x`-prefixed hex strings.
+///
+/// This can be used as a serde field attribute: `#[serde(with = "hex")]`.
+pub mod hex {
+	use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+	/// Write `bytes` as a `0x`-prefixed lowercase hex string.
+	pub fn encode(bytes: &[u8]) -> String {
+		let mut hex = String::with_capacity(2 + bytes.len() * 2);
+		hex.push_str("0x");
+		for byte in bytes {
+			hex.push_str(&format!("{:02x}", byte));
+		}
+		hex
+	}
+
+	/// Read a hex string of exactly `N` bytes. The `0x` prefix is optional.
+	pub fn decode<const N: usize>(hex: &str) -> Result<[u8; N], &'static str> {
+		let hex = hex.strip_prefix("0x").unwrap_or(hex);
+		if hex.len() != N * 2 {
+			return Err("hex string has the wrong length");
+		}
+		let mut bytes = [0u8; N];
+		for (i, byte) in bytes.iter_mut().enumerate() {
+			let digits = hex.get(i * 2..i * 2 + 2).ok_or("hex string is not ascii")?;
+			*byte = u8::from_str_radix(digits, 16).map_err(|_| "invalid hex digit")?;
+		}
+		Ok(bytes)
+	}
+
+	pub fn serialize<S: Serializer, const N: usize>(
+		bytes: &[u8; N],
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&encode(bytes))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+		deserializer: D,
+	) -> Result<[u8; N], D::Error> {
+		let hex = String::deserialize(deserializer)?;
+		decode(&hex).map_err(D::Error::custom)
+	}
+}
+
+/// Hash some raw bytes.
+pub fn hash(data: &[u8]) -> Hash {
+	Sha256::digest(data).into()
 }
 
 /// The Result type for our runtime. When everything completes successfully, we return `Ok(())`,
-/// otherwise return a static error message.
-pub type DispatchResult = Result<(), &'static str>;
+/// otherwise return a `DispatchError` describing what went wrong.
+pub type DispatchResult = Result<(), DispatchError>;
+
+/// The reason a call or a block failed to execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchError {
+	/// An error declared by one of the pallets of the runtime.
+	Module(ModuleError),
+	/// The origin of the call is not allowed to make it.
+	BadOrigin,
+	/// Any other error, described by a static message.
+	Other(&'static str),
+}
+
+/// A pallet error, identified by where it lives in the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleError {
+	/// The index of the pallet in the runtime, in declaration order.
+	pub index: u8,
+	/// The index of the error variant in the pallet's `Error` enum.
+	pub error: u8,
+	/// The name of the error variant, for humans.
+	pub name: &'static str,
+}
+
+impl TypeInfo for DispatchError {
+	fn type_name() -> String {
+		"DispatchError".to_string()
+	}
+}
+
+impl core::fmt::Display for DispatchError {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			DispatchError::Module(ModuleError { index, error, name }) => {
+				write!(f, "{} (pallet {}, error {})", name, index, error)
+			},
+			DispatchError::BadOrigin => write!(f, "bad origin"),
+			DispatchError::Other(message) => write!(f, "{}", message),
+		}
+	}
+}
+
+/// Each pallet declares an `Error` enum and implements this trait for it, so its variants can be
+/// identified by index and name.
+pub trait PalletError: Copy + 'static {
+	/// Every variant of the `Error` enum, in order.
+	const VARIANTS: &'static [Self];
+	/// The index of this variant in the `Error` enum.
+	fn index(&self) -> u8;
+	/// The name of this variant.
+	fn name(&self) -> &'static str;
+	/// The doc comment of this variant, for the metadata.
+	fn docs(&self) -> &'static str;
+}
+
+/// The index of a pallet in the runtime, keyed by the pallet's `Error` type.
+///
+/// This is implemented by the `#[macros::runtime]` expansion, since only the runtime knows the
+/// order in which its pallets are declared. The system pallet comes first in every runtime, so it
+/// implements this itself.
+pub trait PalletIndex {
+	const INDEX: u8;
+}
+
+impl<E: PalletError + PalletIndex> From<E> for DispatchError {
+	fn from(error: E) -> Self {
+		DispatchError::Module(ModuleError {
+			index: E::INDEX,
+			error: error.index(),
+			name: error.name(),
+		})
+	}
+}
+
+/// Run `f` as a transaction over `storage`: if it returns `Ok` its changes are kept, and if it
+/// returns `Err` every change it made to `storage` is reverted.
+///
+/// Transactions can be nested, for example when a call dispatches other calls: an inner
+/// transaction which fails only reverts its own changes, and the outer one decides what happens
+/// to the rest. Only storage is reverted: state kept outside of it, like the events a pallet has
+/// not handed over yet, is up to the caller.
+pub fn with_transaction<B: Backend, R, E>(
+	storage: &Storage<B>,
+	f: impl FnOnce() -> Result<R, E>,
+) -> Result<R, E> {
+	storage.start_transaction();
+	let result = f();
+	match result {
+		Ok(_) => storage.commit_transaction(),
+		Err(_) => storage.rollback_transaction(),
+	}
+	result
+}
+
+/// The cost of executing a call, in abstract units. Blocks have a maximum total weight.
+pub type Weight = u64;
+
+/// Who a call is made by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin<AccountId> {
+	/// The call has the highest privileges, and is not made by any account.
+	Root,
+	/// The call is made by an account, which signed it.
+	Signed(AccountId),
+	/// The call is not made by anyone in particular.
+	None,
+}
+
+/// Get the account which made a call, or return `BadOrigin` if it is not signed.
+pub fn ensure_signed<AccountId>(origin: Origin<AccountId>) -> Result<AccountId, DispatchError> {
+	match origin {
+		Origin::Signed(who) => Ok(who),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
+
+/// Return `BadOrigin` unless the call is made with the `Root` origin.
+pub fn ensure_root<AccountId>(origin: Origin<AccountId>) -> DispatchResult {
+	match origin {
+		Origin::Root => Ok(()),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
 
 /// A trait which allows us to dispatch an incoming extrinsic to the appropriate state transition
 /// function call.
 pub trait Dispatch {
-	/// The type used to identify the caller of the function.
-	type Caller;
-	/// The state transition function call the caller is trying to access.
+	/// The type used to identify who makes the call.
+	type Origin;
+	/// The state transition function call the origin is trying to access.
 	type Call;
 
-	/// This function takes a `caller` and the `call` they want to make, and returns a `Result`
+	/// This function takes an `origin` and the `call` it wants to make, and returns a `Result`
 	/// based on the outcome of that function call.
-	fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
-}
\ No newline at end of file
+	fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> DispatchResult;
+
+	/// The weight of `call`, which is known before the call is dispatched.
+	fn weight(call: &Self::Call) -> Weight;
+}
+
+/// Functions a pallet runs at the boundaries of every block, for work which is not triggered by
+/// an extrinsic. The runtime calls them on every pallet in the order they are declared, and counts
+/// the weight they return toward the block, even beyond its maximum weight.
+pub trait Hooks<BlockNumber> {
+	/// Called at the start of the block `block_number`, before its extrinsics.
+	fn on_initialize(&mut self, _block_number: BlockNumber) -> Weight {
+		0
+	}
+
+	/// Called at the end of the block `block_number`, after its extrinsics.
+	fn on_finalize(&mut self, _block_number: BlockNumber) -> Weight {
+		0
+	}
+}
+
+/// Calls a pallet makes with another origin. A pallet cannot reach the other pallets, so it queues
+/// these calls, and the runtime dispatches them right after the call to the pallet which queued
+/// them, each in its own storage transaction. A queued call which fails does not fail the call
+/// which queued it: its result is handed back to the pallet instead. Every pallet implements this,
+/// and most of them never queue anything.
+///
+/// Events are not in storage, so a queued call which fails keeps the events it deposited before
+/// failing. The calls of the pallets only deposit events once they cannot fail anymore.
+pub trait DispatchQueue<Origin, Call> {
+	/// Take the calls queued since the last time this was called, with the origin to dispatch each
+	/// of them with.
+	fn take_dispatches(&mut self) -> Vec<(Origin, Call)> {
+		Vec::new()
+	}
+
+	/// Called by the runtime with the result of each queued call, in the order they were queued,
+	/// along with the origin it was dispatched with.
+	fn dispatched(&mut self, _origin: Origin, _result: DispatchResult) {}
+}
+
+/// How a runtime charges the fee of each extrinsic. Fees are paid with whatever the runtime keeps
+/// balances in, so the runtime implements this itself, usually by calling one of its pallets.
+pub trait TransactionPayment<AccountId> {
+	/// The type fees are paid in.
+	type Balance;
+
+	/// The fee paid by the caller of an extrinsic, from the weight of its call and its encoded
+	/// length in bytes.
+	fn fee(weight: Weight, length: usize) -> Self::Balance;
+
+	/// Check that `who` can pay `fee`, without withdrawing it.
+	fn can_pay_fee(&self, who: &AccountId, fee: Self::Balance) -> DispatchResult;
+
+	/// Withdraw `fee` from `who`. It is not refunded if the call of the extrinsic fails.
+	fn withdraw_fee(&mut self, who: &AccountId, fee: Self::Balance) -> DispatchResult;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		ensure_root, ensure_signed, with_transaction, DispatchError, DispatchResult, Origin, PublicKey,
+		Signature,
+	};
+	use crate::storage::{MemoryBackend, Storage, StorageMap};
+
+	// A storage map whose keys are the state, starting with a single key.
+	fn test_state() -> (Storage<MemoryBackend>, StorageMap<u8, bool, MemoryBackend>) {
+		let storage = Storage::default();
+		let mut state = StorageMap::new(&storage, "Test", "State");
+		state.insert(&1, &true);
+		(storage, state)
+	}
+
+	fn keys(state: &StorageMap<u8, bool, MemoryBackend>) -> Vec<u8> {
+		state.iter().into_iter().map(|(key, _)| key).collect()
+	}
+
+	#[test]
+	fn transaction_commits_on_ok() {
+		let (storage, mut state) = test_state();
+		let result: DispatchResult = with_transaction(&storage, || {
+			state.insert(&2, &true);
+			Ok(())
+		});
+		assert_eq!(result, Ok(()));
+		assert_eq!(keys(&state), vec![1, 2]);
+	}
+
+	#[test]
+	fn transaction_reverts_on_err() {
+		let (storage, mut state) = test_state();
+		let result: Result<(), _> = with_transaction(&storage, || {
+			state.insert(&2, &true);
+			state.remove(&1);
+			Err(DispatchError::Other("failed"))
+		});
+		assert_eq!(result, Err(DispatchError::Other("failed")));
+		assert_eq!(keys(&state), vec![1]);
+	}
+
+	#[test]
+	fn nested_transactions() {
+		let (storage, mut state) = test_state();
+		let result: DispatchResult = with_transaction(&storage, || {
+			state.insert(&2, &true);
+			// The inner failure only reverts the inner write.
+			let inner: Result<(), _> = with_transaction(&storage, || {
+				state.insert(&3, &true);
+				Err(DispatchError::Other("inner failed"))
+			});
+			assert!(inner.is_err());
+			with_transaction(&storage, || {
+				state.insert(&4, &true);
+				Ok(())
+			})
+		});
+		assert_eq!(result, Ok(()));
+		assert_eq!(keys(&state), vec![1, 2, 4]);
+
+		// An outer failure reverts the committed inner writes too.
+		let result: Result<(), _> = with_transaction(&storage, || {
+			let inner: DispatchResult = with_transaction(&storage, || {
+				state.insert(&5, &true);
+				Ok(())
+			});
+			inner?;
+			Err(DispatchError::Other("outer failed"))
+		});
+		assert!(result.is_err());
+		assert_eq!(keys(&state), vec![1, 2, 4]);
+	}
+
+	#[test]
+	fn ensure_origins() {
+		assert_eq!(ensure_signed(Origin::Signed(1)), Ok(1));
+		assert_eq!(ensure_signed(Origin::<u32>::Root), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_signed(Origin::<u32>::None), Err(DispatchError::BadOrigin));
+
+		assert_eq!(ensure_root(Origin::<u32>::Root), Ok(()));
+		assert_eq!(ensure_root(Origin::Signed(1)), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_root(Origin::<u32>::None), Err(DispatchError::BadOrigin));
+	}
+
+	#[test]
+	fn reject_weak_keys() {
+		// The identity point is a key of small order: with it, this signature passes the lenient
+		// checks of ed25519 for any message.
+		let mut identity = [0; 32];
+		identity[0] = 1;
+		let mut signature = [0; 64];
+		signature[0] = 1;
+		assert!(!PublicKey(identity).verify(b"message", &Signature(signature)));
+	}
+}
@@ -1,35 +1,240 @@
-use std::{collections::BTreeMap, ops::AddAssign};
+use std::ops::AddAssign;
 use num::{traits::Zero, One};
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{self, Decode, Encode};
+use crate::storage::{Entries, PalletStorage, Storage, StorageMap, StorageValue};
+use crate::support::{DispatchResult, Hash, Header, Hooks, PalletError, PalletIndex, Weight};
 
 pub trait Config {
-	type AccountId: Ord + Clone;
-	type BlockNumber: Zero + One + AddAssign + Copy;
-	type Nonce: Zero + One + Copy;
+	type AccountId: Ord + Clone + Encode + Decode;
+	type BlockNumber: Zero + One + AddAssign + Copy + Encode + Decode;
+	type Nonce: Zero + One + Copy + Ord + Encode + Decode;
+	/// The backend the storage items of every pallet live in.
+	type Backend: crate::storage::Backend + Default;
+	/// The aggregated event type of the runtime, which is stored by this pallet.
+	type RuntimeEvent;
+	/// The maximum total weight of the extrinsics of a block.
+	const MAX_BLOCK_WEIGHT: Weight;
+	/// The maximum total encoded length of the extrinsics of a block, in bytes.
+	const MAX_BLOCK_LENGTH: usize;
+	/// The maximum number of calls nested in one another, like `sudo` calls, including the
+	/// outermost call. Deeper calls cannot be decoded nor dispatched.
+	const MAX_CALL_DEPTH: u32;
 	// and more if needed
 }
 
+/// The origin of the calls of a runtime using this pallet.
+pub type OriginFor<T> = crate::support::Origin<<T as Config>::AccountId>;
+
+/// The part of the block during which an event was deposited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+	/// The hooks run at the start of the block.
+	Initialization,
+	/// The extrinsic at this index of the block.
+	ApplyExtrinsic(u32),
+	/// The hooks run at the end of the block.
+	Finalization,
+}
+
+/// An event deposited during the current block, along with the part of the block which produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRecord<Event> {
+	/// When the event was deposited.
+	pub phase: Phase,
+	/// The event itself.
+	pub event: Event,
+}
+
+/// The errors which can be returned by the System Pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The signature of the extrinsic does not match its caller.
+	BadSignature,
+	/// The extrinsic uses a nonce which the caller has already used.
+	StaleNonce,
+	/// The extrinsic uses a nonce higher than the next nonce of the caller.
+	FutureNonce,
+	/// The extrinsic would make the block exceed its maximum weight.
+	BlockWeightExceeded,
+	/// The call of the extrinsic has more than `MAX_CALL_DEPTH` nested calls.
+	CallTooDeep,
+	/// The block number of the header does not follow the last imported block.
+	InvalidBlockNumber,
+	/// The parent hash of the header is not the hash of the last imported block.
+	InvalidParentHash,
+	/// The extrinsics root of the header does not match the extrinsics of the block.
+	BadExtrinsicsRoot,
+	/// The extrinsics of the block exceed its maximum length.
+	BlockLengthExceeded,
+	/// The state root of the header does not match the state after executing the block.
+	BadStateRoot,
+	/// The transaction pool already has a transaction of the caller with the same nonce.
+	AlreadyInPool,
+	/// The transaction uses a nonce too far above the next nonce of its caller to be held in the
+	/// transaction pool.
+	NonceTooFar,
+	/// The transaction pool holds as many transactions as it can.
+	PoolFull,
+}
+
+impl PalletError for Error {
+	const VARIANTS: &'static [Self] = &[
+		Error::BadSignature,
+		Error::StaleNonce,
+		Error::FutureNonce,
+		Error::BlockWeightExceeded,
+		Error::CallTooDeep,
+		Error::InvalidBlockNumber,
+		Error::InvalidParentHash,
+		Error::BadExtrinsicsRoot,
+		Error::BlockLengthExceeded,
+		Error::BadStateRoot,
+		Error::AlreadyInPool,
+		Error::NonceTooFar,
+		Error::PoolFull,
+	];
+
+	fn index(&self) -> u8 {
+		*self as u8
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Error::BadSignature => "BadSignature",
+			Error::StaleNonce => "StaleNonce",
+			Error::FutureNonce => "FutureNonce",
+			Error::BlockWeightExceeded => "BlockWeightExceeded",
+			Error::CallTooDeep => "CallTooDeep",
+			Error::InvalidBlockNumber => "InvalidBlockNumber",
+			Error::InvalidParentHash => "InvalidParentHash",
+			Error::BadExtrinsicsRoot => "BadExtrinsicsRoot",
+			Error::BlockLengthExceeded => "BlockLengthExceeded",
+			Error::BadStateRoot => "BadStateRoot",
+			Error::AlreadyInPool => "AlreadyInPool",
+			Error::NonceTooFar => "NonceTooFar",
+			Error::PoolFull => "PoolFull",
+		}
+	}
+
+	fn docs(&self) -> &'static str {
+		match self {
+			Error::BadSignature => "The signature of the extrinsic does not match its caller.",
+			Error::StaleNonce => "The extrinsic uses a nonce which the caller has already used.",
+			Error::FutureNonce => "The extrinsic uses a nonce higher than the next nonce of the caller.",
+			Error::BlockWeightExceeded => "The extrinsic would make the block exceed its maximum weight.",
+			Error::CallTooDeep => "The call of the extrinsic has more than `MAX_CALL_DEPTH` nested calls.",
+			Error::InvalidBlockNumber => "The block number of the header does not follow the last imported block.",
+			Error::InvalidParentHash => "The parent hash of the header is not the hash of the last imported block.",
+			Error::BadExtrinsicsRoot => "The extrinsics root of the header does not match the extrinsics of the block.",
+			Error::BlockLengthExceeded => "The extrinsics of the block exceed its maximum length.",
+			Error::BadStateRoot => "The state root of the header does not match the state after executing the block.",
+			Error::AlreadyInPool => "The transaction pool already has a transaction of the caller with the same nonce.",
+			Error::NonceTooFar => "The transaction uses a nonce too far above the next nonce of its caller to be held in the transaction pool.",
+			Error::PoolFull => "The transaction pool holds as many transactions as it can.",
+		}
+	}
+}
+
+// The system pallet is the first pallet of every runtime.
+impl PalletIndex for Error {
+	const INDEX: u8 = 0;
+}
+
 /// This is the System Pallet.
 /// It handles low level state needed for your blockchain.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(bound(serialize = "T::AccountId: Serialize, T::BlockNumber: Serialize, T::Nonce: Serialize"))]
 pub struct Pallet<T: Config> {
+    /// The storage shared by every pallet of the runtime.
+    #[serde(skip)]
+    storage: Storage<T::Backend>,
     /// The current block number.
-    block_number: T::BlockNumber,
+    block_number: StorageValue<T::BlockNumber, T::Backend>,
+    /// The hash of the genesis block, which identifies the chain.
+    #[serde(serialize_with = "serialize_hash")]
+    genesis_hash: StorageValue<Hash, T::Backend>,
+    /// The header of the genesis block. Like the hash of the last imported block, it is not in
+    /// storage: the state root of the genesis block is taken before the genesis hash is stored.
+    #[serde(skip)]
+    genesis_header: Header<T::BlockNumber>,
+    /// The hash of the header of the last imported block. It is not in storage, because the state
+    /// root of a block cannot commit to the hash of that same block: the node keeps track of it.
+    #[serde(serialize_with = "crate::support::hex::serialize")]
+    parent_hash: Hash,
     /// A map from an account to their nonce.
-    nonce: BTreeMap<T::AccountId, T::Nonce>,
+    nonce: StorageMap<T::AccountId, T::Nonce, T::Backend>,
+    /// The total weight of the extrinsics applied in the current block.
+    block_weight: StorageValue<Weight, T::Backend>,
+    /// The events deposited during the current block.
+    #[serde(skip)]
+    events: Vec<EventRecord<T::RuntimeEvent>>,
+}
+
+// Hashes are written in hex, like in blocks.
+fn serialize_hash<B: crate::storage::Backend, S: serde::Serializer>(
+	value: &StorageValue<Hash, B>,
+	serializer: S,
+) -> Result<S::Ok, S::Error> {
+	crate::support::hex::serialize(&value.get().unwrap_or_default(), serializer)
+}
+
+/// The initial state of the System Pallet.
+#[derive(Serialize, Deserialize)]
+#[serde(
+	bound(serialize = "T::BlockNumber: Serialize", deserialize = "T::BlockNumber: Deserialize<'de>"),
+	default,
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The block number of the genesis block. The first block built on top of it has the next number.
+	pub block_number: T::BlockNumber,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { block_number: T::BlockNumber::zero() }
+	}
+}
+
+impl<T: Config> GenesisConfig<T> {
+	/// Build the System Pallet with this initial state, in `storage`.
+	pub fn build(self, storage: &Storage<T::Backend>) -> Pallet<T> {
+		let mut pallet = Pallet::new(storage);
+		pallet.block_number.put(&self.block_number);
+		pallet
+	}
 }
 
 impl<T: Config> Pallet<T> {
-    pub fn new() -> Self {
+    pub fn new(storage: &Storage<T::Backend>) -> Self {
         Self {
-            block_number: T::BlockNumber::zero(),
-            nonce: BTreeMap::new(),
+            storage: storage.clone(),
+            block_number: StorageValue::new(storage, "System", "BlockNumber"),
+            genesis_hash: StorageValue::new(storage, "System", "GenesisHash"),
+            genesis_header: Header {
+                block_number: T::BlockNumber::zero(),
+                parent_hash: Hash::default(),
+                state_root: Hash::default(),
+                extrinsics_root: Hash::default(),
+            },
+            parent_hash: Hash::default(),
+            nonce: StorageMap::new(storage, "System", "Nonce"),
+            block_weight: StorageValue::new(storage, "System", "BlockWeight"),
+            events: Vec::new(),
         }
     }
 
+	/// Get the backend shared by every pallet of the runtime, to run transactions on it.
+	pub fn backend(&self) -> &Storage<T::Backend> {
+		&self.storage
+	}
+
     /// Get the current block number.
 	pub fn block_number(&self) -> T::BlockNumber {
 		/* TODO: Return the current block number. */
-		self.block_number
+		self.block_number.get().unwrap_or_else(T::BlockNumber::zero)
 	}
 
 	// This function can be used to increment the block number.
@@ -37,35 +242,160 @@ impl<T: Config> Pallet<T> {
 	pub fn inc_block_number(&mut self) {
 		/* TODO: Increment the current block number by one. */
 		// crashes when block_number overflows
-		self.block_number += T::BlockNumber::one()
+		let mut block_number = self.block_number();
+		block_number += T::BlockNumber::one();
+		self.block_number.put(&block_number);
 	}
 
 	// Increment the nonce of an account. This helps us keep track of how many transactions each
 	// account has made.
 	pub fn inc_nonce(&mut self, who: &T::AccountId) {
 		/* TODO: Get the current nonce of `who`, and increment it by one. */
-		let nonce = *self.nonce.get(who).unwrap_or(&T::Nonce::zero());
-		self.nonce.insert(who.clone(), nonce + T::Nonce::one());
+		let nonce = self.get_nonce(who);
+		self.nonce.insert(who, &(nonce + T::Nonce::one()));
+	}
+
+	/// Get the hash of the genesis block. Extrinsics sign it, so they are only valid on this chain.
+	pub fn genesis_hash(&self) -> Hash {
+		self.genesis_hash.get().unwrap_or_default()
+	}
+
+	/// Get the header of the genesis block, as sealed by the runtime.
+	pub fn genesis_header(&self) -> &Header<T::BlockNumber> {
+		&self.genesis_header
+	}
+
+	// Record the header of the genesis block. Its hash is also the parent of the first block.
+	pub fn set_genesis_header(&mut self, header: Header<T::BlockNumber>) {
+		let hash = header.hash();
+		self.genesis_hash.put(&hash);
+		self.parent_hash = hash;
+		self.genesis_header = header;
+	}
+
+	/// Get the hash of the last imported block, which is the parent of the next block.
+	pub fn parent_hash(&self) -> Hash {
+		self.parent_hash
+	}
+
+	// Record the hash of the block which was just imported, or which was imported last before the
+	// node restarted.
+	pub fn set_parent_hash(&mut self, hash: Hash) {
+		self.parent_hash = hash;
+	}
+
+	// Deposit an event produced during `phase` of the current block.
+	pub fn deposit_event(&mut self, phase: Phase, event: T::RuntimeEvent) {
+		self.events.push(EventRecord { phase, event });
 	}
 
-	pub fn get_nonce(&mut self, who: &T::AccountId) -> T::Nonce {
-		*self.nonce.get(who).unwrap_or(&T::Nonce::zero())
+	/// Get the events deposited during the current block.
+	pub fn events(&self) -> &[EventRecord<T::RuntimeEvent>] {
+		&self.events
+	}
+
+	// Remove all the events of the previous block. Called when a new block starts.
+	pub fn reset_events(&mut self) {
+		self.events.clear();
+	}
+
+	// Replace the events of the current block, and return the previous ones. Events are not in
+	// storage, so the runtime puts them back itself when it reverts a block.
+	pub fn replace_events(
+		&mut self,
+		events: Vec<EventRecord<T::RuntimeEvent>>,
+	) -> Vec<EventRecord<T::RuntimeEvent>> {
+		core::mem::replace(&mut self.events, events)
+	}
+
+	/// Get the total weight of the extrinsics applied in the current block.
+	pub fn block_weight(&self) -> Weight {
+		self.block_weight.get().unwrap_or_default()
+	}
+
+	// Add the weight of an extrinsic to the current block, unless it would exceed the maximum
+	// block weight.
+	pub fn register_weight(&mut self, weight: Weight) -> DispatchResult {
+		let block_weight = self
+			.block_weight()
+			.checked_add(weight)
+			.filter(|block_weight| *block_weight <= T::MAX_BLOCK_WEIGHT)
+			.ok_or(Error::BlockWeightExceeded)?;
+		self.block_weight.put(&block_weight);
+		Ok(())
+	}
+
+	// Add weight which the block must carry whatever its maximum, like the weight of the hooks.
+	pub fn register_mandatory_weight(&mut self, weight: Weight) {
+		let block_weight = self.block_weight().saturating_add(weight);
+		self.block_weight.put(&block_weight);
+	}
+
+	// Reset the weight of the previous block. Called when a new block starts.
+	pub fn reset_block_weight(&mut self) {
+		self.block_weight.put(&0);
+	}
+
+	// Get the nonce of an account. This is the nonce the next extrinsic of `who` must use.
+	pub fn get_nonce(&self, who: &T::AccountId) -> T::Nonce {
+		self.nonce.get(who).unwrap_or_else(T::Nonce::zero)
+	}
+
+	/// Check that `nonce` is the next nonce of `who`, so an extrinsic cannot be replayed.
+	pub fn check_nonce(&self, who: &T::AccountId, nonce: T::Nonce) -> DispatchResult {
+		let expected = self.get_nonce(who);
+		if nonce < expected {
+			return Err(Error::StaleNonce.into());
+		}
+		if nonce > expected {
+			return Err(Error::FutureNonce.into());
+		}
+		Ok(())
+	}
+}
+
+// The block number is incremented by the runtime itself, before any hook runs.
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+impl<T: Config> PalletStorage for Pallet<T> {
+	fn storage(&self) -> Entries {
+		[
+			self.block_number.entries(),
+			self.genesis_hash.entries(),
+			self.nonce.entries(),
+			self.block_weight.entries(),
+		]
+		.concat()
+	}
+
+	fn restore(&mut self, storage: &[(Vec<u8>, Vec<u8>)]) -> Result<(), codec::Error> {
+		self.block_number.restore(storage)?;
+		self.genesis_hash.restore(storage)?;
+		self.nonce.restore(storage)?;
+		self.block_weight.restore(storage)
 	}
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Error, Phase};
+
     struct TestConfig;
 
 	impl super::Config for TestConfig {
 		type AccountId = String;
 		type BlockNumber = u32;
 		type Nonce = u32;
+		type Backend = crate::storage::MemoryBackend;
+		type RuntimeEvent = &'static str;
+		const MAX_BLOCK_WEIGHT: crate::support::Weight = 100;
+		const MAX_BLOCK_LENGTH: usize = 100;
+		const MAX_CALL_DEPTH: u32 = 4;
 	}
 
 	#[test]
     fn init_system() {
-		let system: super::Pallet<TestConfig> = super::Pallet::new();
+		let system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
 		assert_eq!(system.block_number(), 0);
 		/* TODO: Create a test which checks the following:
 			- Increment the current block number.
@@ -77,7 +407,7 @@ mod tests {
 
 	#[test]
 	fn inc_block_number() {
-		let mut system: super::Pallet<TestConfig> = super::Pallet::new();
+		let mut system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
 		system.inc_block_number();
 		assert_eq!(system.block_number(), 1);
 	}
@@ -85,9 +415,63 @@ mod tests {
 	#[test]
 	fn inc_nonce() {
 		let alice = String::from("alice");
-		let mut system: super::Pallet<TestConfig> = super::Pallet::new();
+		let mut system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
 		system.inc_nonce(&alice);
 
 		assert_eq!(system.get_nonce(&alice), 1);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn check_nonce() {
+		let alice = String::from("alice");
+		let mut system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
+		assert_eq!(system.check_nonce(&alice, 0), Ok(()));
+		assert_eq!(system.check_nonce(&alice, 1), Err(Error::FutureNonce.into()));
+
+		system.inc_nonce(&alice);
+		assert_eq!(system.check_nonce(&alice, 0), Err(Error::StaleNonce.into()));
+		assert_eq!(system.check_nonce(&alice, 1), Ok(()));
+	}
+
+	#[test]
+	fn register_weight() {
+		let mut system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
+		assert_eq!(system.register_weight(60), Ok(()));
+		assert_eq!(system.register_weight(40), Ok(()));
+		assert_eq!(system.block_weight(), 100);
+
+		assert_eq!(system.register_weight(1), Err(Error::BlockWeightExceeded.into()));
+		assert_eq!(system.block_weight(), 100);
+
+		// Mandatory weight is counted even beyond the maximum.
+		system.register_mandatory_weight(50);
+		assert_eq!(system.block_weight(), 150);
+		assert_eq!(system.register_weight(1), Err(Error::BlockWeightExceeded.into()));
+
+		system.reset_block_weight();
+		assert_eq!(system.block_weight(), 0);
+	}
+
+	#[test]
+	fn deposit_and_reset_events() {
+		let mut system: super::Pallet<TestConfig> = super::Pallet::new(&Default::default());
+		system.deposit_event(Phase::Initialization, "first");
+		system.deposit_event(Phase::ApplyExtrinsic(2), "second");
+
+		assert_eq!(
+			system.events(),
+			&[
+				super::EventRecord { phase: Phase::Initialization, event: "first" },
+				super::EventRecord { phase: Phase::ApplyExtrinsic(2), event: "second" },
+			]
+		);
+
+		let events = system.replace_events(Vec::new());
+		assert_eq!(events.len(), 2);
+		assert!(system.events().is_empty());
+
+		system.replace_events(events);
+		system.reset_events();
+		assert!(system.events().is_empty());
+	}
+}
@@ -0,0 +1,128 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::support::{DispatchError, DispatchResult};
+
+/// A part of the execution of the runtime, which a subscriber can follow.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span {
+	/// The execution of the block `number`.
+	Block { number: String },
+	/// The application of the extrinsic at `index` of the current block, signed by `caller`.
+	Extrinsic { index: u32, caller: String },
+	/// The dispatch of the call `call` of the pallet `pallet`, on behalf of `origin`. Calls made by
+	/// other calls, like the ones of `sudo`, have their own span inside the span of their parent.
+	Call { pallet: &'static str, call: &'static str, origin: String },
+}
+
+impl core::fmt::Display for Span {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Span::Block { number } => write!(f, "block {}", number),
+			Span::Extrinsic { index, caller } => write!(f, "extrinsic {} from {}", index, caller),
+			Span::Call { pallet, call, origin } => write!(f, "call {}::{} by {}", pallet, call, origin),
+		}
+	}
+}
+
+/// Receives the spans of the execution of the runtime, for example to log them.
+pub trait Subscriber {
+	/// Execution entered `span`.
+	fn enter(&self, span: &Span);
+	/// Execution left `span`, with `outcome`.
+	fn exit(&self, span: &Span, outcome: &DispatchResult);
+}
+
+thread_local! {
+	static SUBSCRIBER: RefCell<Option<Rc<dyn Subscriber>>> = RefCell::new(None);
+}
+
+/// Send the spans of the runtime executed on this thread to `subscriber`, or to nobody if it is
+/// `None`, which is the default. Returns the previous subscriber.
+pub fn set_subscriber(subscriber: Option<Rc<dyn Subscriber>>) -> Option<Rc<dyn Subscriber>> {
+	SUBSCRIBER.with(|current| current.replace(subscriber))
+}
+
+/// Run `f` inside the span made by `span`. The span is only made if there is a subscriber, so
+/// tracing costs nothing when nobody listens.
+pub fn in_span<R>(
+	span: impl FnOnce() -> Span,
+	f: impl FnOnce() -> Result<R, DispatchError>,
+) -> Result<R, DispatchError> {
+	let Some(subscriber) = SUBSCRIBER.with(|current| current.borrow().clone()) else {
+		return f();
+	};
+	let span = span();
+	subscriber.enter(&span);
+	let result = f();
+	subscriber.exit(&span, &result.as_ref().map(|_| ()).map_err(|e| *e));
+	result
+}
+
+/// A subscriber which prints every span to stderr, indented by depth.
+#[derive(Default)]
+pub struct Logger {
+	depth: Cell<usize>,
+}
+
+impl Subscriber for Logger {
+	fn enter(&self, span: &Span) {
+		eprintln!("{:indent$}> {}", "", span, indent = self.depth.get() * 2);
+		self.depth.set(self.depth.get() + 1);
+	}
+
+	fn exit(&self, span: &Span, outcome: &DispatchResult) {
+		self.depth.set(self.depth.get().saturating_sub(1));
+		match outcome {
+			Ok(()) => eprintln!("{:indent$}< {}: ok", "", span, indent = self.depth.get() * 2),
+			Err(e) => eprintln!("{:indent$}< {}: {}", "", span, e, indent = self.depth.get() * 2),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	use super::{in_span, set_subscriber, Span, Subscriber};
+	use crate::support::{DispatchError, DispatchResult};
+
+	#[derive(Default)]
+	struct Recorder(RefCell<Vec<String>>);
+
+	impl Subscriber for Recorder {
+		fn enter(&self, span: &Span) {
+			self.0.borrow_mut().push(format!("> {}", span));
+		}
+
+		fn exit(&self, span: &Span, outcome: &DispatchResult) {
+			self.0.borrow_mut().push(format!("< {}: {:?}", span, outcome));
+		}
+	}
+
+	#[test]
+	fn nested_spans() {
+		let block = || Span::Block { number: "1".to_string() };
+		let call = || Span::Call { pallet: "balances", call: "transfer", origin: "Root".to_string() };
+
+		// Nothing is made without a subscriber.
+		assert_eq!(in_span(|| unreachable!(), || Ok(1)), Ok(1));
+
+		let recorder = Rc::new(Recorder::default());
+		set_subscriber(Some(recorder.clone()));
+		let result = in_span(block, || in_span(call, || Err::<(), _>(DispatchError::BadOrigin)));
+		assert!(set_subscriber(None).is_some());
+
+		assert_eq!(result, Err(DispatchError::BadOrigin));
+		assert_eq!(
+			recorder.0.take(),
+			vec![
+				"> block 1",
+				"> call balances::transfer by Root",
+				"< call balances::transfer by Root: Err(BadOrigin)",
+				"< block 1: Err(BadOrigin)",
+			]
+		);
+	}
+}
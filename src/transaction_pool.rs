@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use crate::support::DispatchResult;
+use crate::{system, types, Runtime};
+
+/// The number of transactions a pool holds at most, unless it is built with other limits.
+pub const MAX_TRANSACTIONS: usize = 1024;
+
+/// How far above the next nonce of its caller the nonce of a transaction can be, unless the pool
+/// is built with other limits.
+pub const MAX_NONCE_GAP: types::Nonce = 16;
+
+/// A pool of transactions which have been submitted but are not in a block yet.
+///
+/// Transactions are validated against the current state of the runtime when they are submitted.
+/// Invalid transactions are dropped. A transaction which uses a nonce higher than the next nonce of
+/// its caller is kept until the transactions before it are included, and it becomes ready.
+///
+/// The pool is bounded, so that submitting transactions cannot make it grow without limit: it
+/// holds at most `max_transactions` transactions, and only those whose nonce is at most
+/// `max_nonce_gap` above the next nonce of their caller.
+#[derive(Debug)]
+pub struct TransactionPool {
+	/// The transactions of each caller, by nonce.
+	transactions: BTreeMap<types::AccountId, BTreeMap<types::Nonce, types::Extrinsic>>,
+	/// The number of transactions the pool holds at most.
+	max_transactions: usize,
+	/// How far above the next nonce of its caller the nonce of a transaction can be.
+	max_nonce_gap: types::Nonce,
+}
+
+impl TransactionPool {
+	/// Create an empty pool, with the default limits `MAX_TRANSACTIONS` and `MAX_NONCE_GAP`.
+	pub fn new() -> Self {
+		Self::with_limits(MAX_TRANSACTIONS, MAX_NONCE_GAP)
+	}
+
+	/// Create an empty pool, which holds at most `max_transactions` transactions, whose nonces are
+	/// at most `max_nonce_gap` above the next nonce of their caller.
+	pub fn with_limits(max_transactions: usize, max_nonce_gap: types::Nonce) -> Self {
+		Self { transactions: BTreeMap::new(), max_transactions, max_nonce_gap }
+	}
+
+	/// Submit a transaction to the pool. It is rejected if it is invalid against the state of
+	/// `runtime`, if its nonce is too far above the next nonce of its caller, if the pool already
+	/// has a transaction of the same caller with the same nonce, or if the pool is full.
+	pub fn submit(&mut self, runtime: &Runtime, extrinsic: types::Extrinsic) -> DispatchResult {
+		validate(runtime, &extrinsic)?;
+		let next_nonce = runtime.system.get_nonce(&extrinsic.caller);
+		if extrinsic.nonce > next_nonce.saturating_add(self.max_nonce_gap) {
+			return Err(system::Error::NonceTooFar.into());
+		}
+		let queue = self.transactions.get(&extrinsic.caller);
+		if queue.is_some_and(|queue| queue.contains_key(&extrinsic.nonce)) {
+			return Err(system::Error::AlreadyInPool.into());
+		}
+		if self.len() >= self.max_transactions {
+			return Err(system::Error::PoolFull.into());
+		}
+		self.transactions.entry(extrinsic.caller).or_default().insert(extrinsic.nonce, extrinsic);
+		Ok(())
+	}
+
+	/// The transactions which can be applied on top of the state of `runtime`, in order. For each
+	/// caller, these are the transactions which follow the next nonce without a gap.
+	pub fn ready(&self, runtime: &Runtime) -> Vec<types::Extrinsic> {
+		let mut ready = Vec::new();
+		for (caller, queue) in &self.transactions {
+			let mut nonce = runtime.system.get_nonce(caller);
+			while let Some(extrinsic) = queue.get(&nonce) {
+				ready.push(extrinsic.clone());
+				nonce += 1;
+			}
+		}
+		ready
+	}
+
+	/// The nonce for the next transaction of `who`: the one following its ready transactions.
+	pub fn next_nonce(&self, runtime: &Runtime, who: &types::AccountId) -> types::Nonce {
+		let mut nonce = runtime.system.get_nonce(who);
+		if let Some(queue) = self.transactions.get(who) {
+			while queue.contains_key(&nonce) {
+				nonce += 1;
+			}
+		}
+		nonce
+	}
+
+	/// Every transaction in the pool, ready or not, ordered by caller and nonce.
+	pub fn transactions(&self) -> Vec<types::Extrinsic> {
+		self.transactions.values().flat_map(|queue| queue.values().cloned()).collect()
+	}
+
+	/// Drop the transactions which are not valid anymore against the state of `runtime`, for
+	/// example because they were included in the last imported block.
+	pub fn prune(&mut self, runtime: &Runtime) {
+		for queue in self.transactions.values_mut() {
+			queue.retain(|_, extrinsic| validate(runtime, extrinsic).is_ok());
+		}
+		self.transactions.retain(|_, queue| !queue.is_empty());
+	}
+
+	/// The number of transactions in the pool, ready or not.
+	pub fn len(&self) -> usize {
+		self.transactions.values().map(|queue| queue.len()).sum()
+	}
+
+	/// Whether the pool has no transactions.
+	pub fn is_empty(&self) -> bool {
+		self.transactions.is_empty()
+	}
+}
+
+// A transaction is valid for the pool if it is valid against the state of `runtime`, or if only its
+// nonce is too high.
+fn validate(runtime: &Runtime, extrinsic: &types::Extrinsic) -> DispatchResult {
+	match runtime.validate_transaction(extrinsic) {
+		Err(e) if e == system::Error::FutureNonce.into() => Ok(()),
+		res => res,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::TransactionPool;
+	use crate::support;
+	use crate::{balances, system, types, GenesisConfig, Keyring, Runtime, RuntimeCall};
+
+	fn new_runtime() -> Runtime {
+		Runtime::from_genesis(GenesisConfig {
+			balances: balances::GenesisConfig { balances: vec![(Keyring::Alice.public(), 100)] },
+			..Default::default()
+		})
+	}
+
+	fn transfer(runtime: &Runtime, from: Keyring, nonce: u32) -> types::Extrinsic {
+		support::Extrinsic::new_signed(
+			&from.pair(),
+			nonce,
+			RuntimeCall::balances(balances::Call::transfer { to: Keyring::Charlie.public(), amount: 1 }),
+			runtime.system.genesis_hash(),
+		)
+	}
+
+	#[test]
+	fn hold_future_transactions() {
+		let runtime = new_runtime();
+		let mut pool = TransactionPool::new();
+
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 2)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 1)), Ok(()));
+		assert_eq!(pool.len(), 2);
+		assert!(pool.ready(&runtime).is_empty());
+		assert_eq!(pool.next_nonce(&runtime, &Keyring::Alice.public()), 0);
+
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)), Ok(()));
+		let nonces = pool.ready(&runtime).iter().map(|extrinsic| extrinsic.nonce).collect::<Vec<_>>();
+		assert_eq!(nonces, vec![0, 1, 2]);
+		assert_eq!(pool.next_nonce(&runtime, &Keyring::Alice.public()), 3);
+		assert_eq!(pool.transactions(), pool.ready(&runtime));
+	}
+
+	#[test]
+	fn reject_invalid_transactions() {
+		let mut runtime = new_runtime();
+		let mut pool = TransactionPool::new();
+
+		// Bob has nothing to pay the fee with.
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Bob, 0)),
+			Err(balances::Error::CannotPayFee.into())
+		);
+
+		let mut forged = transfer(&runtime, Keyring::Bob, 0);
+		forged.caller = Keyring::Alice.public();
+		assert_eq!(pool.submit(&runtime, forged), Err(system::Error::BadSignature.into()));
+
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)), Ok(()));
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)),
+			Err(system::Error::AlreadyInPool.into())
+		);
+
+		runtime.system.inc_nonce(&Keyring::Alice.public());
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)),
+			Err(system::Error::StaleNonce.into())
+		);
+	}
+
+	#[test]
+	fn limit_the_pool() {
+		let runtime = Runtime::from_genesis(GenesisConfig {
+			balances: balances::GenesisConfig {
+				balances: vec![(Keyring::Alice.public(), 100), (Keyring::Bob.public(), 100)],
+			},
+			..Default::default()
+		});
+		let mut pool = TransactionPool::with_limits(3, 2);
+
+		// A transaction can be at most two nonces ahead of the next nonce of its caller.
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 3)),
+			Err(system::Error::NonceTooFar.into())
+		);
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 2)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(&runtime, Keyring::Bob, 0)), Ok(()));
+
+		// The pool holds at most three transactions, whoever their caller is.
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 1)),
+			Err(system::Error::PoolFull.into())
+		);
+		assert_eq!(
+			pool.submit(&runtime, transfer(&runtime, Keyring::Bob, 0)),
+			Err(system::Error::AlreadyInPool.into())
+		);
+		assert_eq!(pool.len(), 3);
+	}
+
+	#[test]
+	fn prune_included_transactions() {
+		let mut runtime = new_runtime();
+		let mut pool = TransactionPool::new();
+		pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 0)).unwrap();
+		pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 1)).unwrap();
+		pool.submit(&runtime, transfer(&runtime, Keyring::Alice, 3)).unwrap();
+
+		let block = runtime.build_block(vec![transfer(&runtime, Keyring::Alice, 0)]);
+		runtime.execute_block(block).unwrap();
+		pool.prune(&runtime);
+
+		assert_eq!(pool.len(), 2);
+		let nonces = pool.ready(&runtime).iter().map(|extrinsic| extrinsic.nonce).collect::<Vec<_>>();
+		assert_eq!(nonces, vec![1]);
+
+		// Without any balance, the remaining transactions of alice cannot pay their fees anymore.
+		runtime.balances.set_balance(&Keyring::Alice.public(), 0);
+		pool.prune(&runtime);
+		assert!(pool.is_empty());
+	}
+}